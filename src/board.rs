@@ -30,13 +30,13 @@ mod utils;
 mod zobrist;
 
 use self::{
-    defs::{Pieces, BB_SQUARES},
+    defs::{CastlingRooks, Pieces, BB_SQUARES, CASTLING_ROOKS_DEFAULT},
     gamestate::GameState,
     history::History,
     zobrist::{ZobristKey, ZobristRandoms},
 };
 use crate::{
-    defs::{Bitboard, NrOf, Piece, Side, Sides, Square, EMPTY},
+    defs::{Bitboard, Castling, NrOf, Piece, Side, Sides, Square, EMPTY},
     evaluation::{
         defs::PIECE_VALUES,
         material,
@@ -55,6 +55,9 @@ pub struct Board {
     pub game_state: GameState,
     pub history: History,
     pub piece_list: [Piece; NrOf::SQUARES],
+    pub castling_rooks: CastlingRooks,
+    castling_mask: [u8; NrOf::SQUARES],
+    pub chess960: bool,
     zr: Arc<ZobristRandoms>,
 }
 
@@ -68,6 +71,9 @@ impl Board {
             game_state: GameState::new(),
             history: History::new(),
             piece_list: [Pieces::NONE; NrOf::SQUARES],
+            castling_rooks: CASTLING_ROOKS_DEFAULT,
+            castling_mask: [Castling::ALL; NrOf::SQUARES],
+            chess960: false,
             zr: Arc::new(ZobristRandoms::new()),
         }
     }
@@ -97,6 +103,13 @@ impl Board {
         self.bb_pieces[side][Pieces::KING].trailing_zeros() as Square
     }
 
+    // Returns true if the side has any pieces other than king and pawns.
+    pub fn has_non_pawn_material(&self, side: Side) -> bool {
+        let king_and_pawns =
+            self.bb_pieces[side][Pieces::KING] | self.bb_pieces[side][Pieces::PAWN];
+        self.bb_side[side] & !king_and_pawns > 0
+    }
+
     // Remove a piece from the board, for the given side, piece, and square.
     pub fn remove_piece(&mut self, side: Side, piece: Piece, square: Square) {
         self.bb_pieces[side][piece] ^= BB_SQUARES[square];
@@ -173,6 +186,20 @@ impl Board {
         self.game_state = GameState::new();
         self.history.clear();
         self.piece_list = [Pieces::NONE; NrOf::SQUARES];
+        self.castling_rooks = CASTLING_ROOKS_DEFAULT;
+        self.castling_mask = [Castling::ALL; NrOf::SQUARES];
+    }
+
+    // Sets up a castling permission for the rook on the given square.
+    // Moving the king or this rook, or capturing the rook, will remove the
+    // permission again.
+    fn set_castling_rook(&mut self, side: Side, wing: usize, rook: Square, king: Square) {
+        let right = Board::castling_right(side, wing);
+
+        self.game_state.castling |= right;
+        self.castling_rooks[side][wing] = rook;
+        self.castling_mask[rook] &= !right;
+        self.castling_mask[king] &= !right;
     }
 
     // Main initialization function. This is used to initialize the "other"
@@ -21,8 +21,8 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-use crate::defs::{Bitboard, NrOf, Piece, Square};
-use std::ops::RangeInclusive;
+use crate::defs::{Bitboard, NrOf, Piece, Sides, Square};
+use std::{fmt, ops::RangeInclusive};
 
 // Exports
 pub use super::zobrist::{ZobristKey, ZobristRandoms};
@@ -58,40 +58,45 @@ pub struct Files;
 impl Files {
     pub const A: usize = 0;
     pub const B: usize = 1;
+    pub const C: usize = 2;
+    pub const D: usize = 3;
+    pub const E: usize = 4;
+    pub const F: usize = 5;
     pub const G: usize = 6;
     pub const H: usize = 7;
 }
 
+// The two sides of the board a king can castle to.
+pub struct Wing;
+impl Wing {
+    pub const KING: usize = 0;
+    pub const QUEEN: usize = 1;
+}
+
+// Start squares of the castling rooks, per side and wing. In Chess960,
+// these are taken from the FEN-string.
+pub type CastlingRooks = [[Square; 2]; Sides::BOTH];
+pub const CASTLING_ROOKS_DEFAULT: CastlingRooks =
+    [[Squares::H1, Squares::A1], [Squares::H8, Squares::A8]];
+
 pub struct Ranks;
 impl Ranks {
     pub const R1: usize = 0;
     pub const R2: usize = 1;
+    pub const R3: usize = 2;
     pub const R4: usize = 3;
     pub const R5: usize = 4;
+    pub const R6: usize = 5;
     pub const R7: usize = 6;
     pub const R8: usize = 7;
 }
 
 pub struct Squares;
 impl Squares {
-    // White side squares that are important for castling
+    // Corners where the castling rooks start in normal chess
     pub const A1: Square = 0;
-    pub const B1: Square = 1;
-    pub const C1: Square = 2;
-    pub const D1: Square = 3;
-    pub const E1: Square = 4;
-    pub const F1: Square = 5;
-    pub const G1: Square = 6;
     pub const H1: Square = 7;
-
-    // Black side squares that are important for castling
     pub const A8: Square = 56;
-    pub const B8: Square = 57;
-    pub const C8: Square = 58;
-    pub const D8: Square = 59;
-    pub const E8: Square = 60;
-    pub const F8: Square = 61;
-    pub const G8: Square = 62;
     pub const H8: Square = 63;
 
     // White EP-squares start/end
@@ -172,3 +177,42 @@ pub enum Direction {
     DownRight,
     DownLeft,
 }
+
+// Errors the FEN-reader can return. The first ones are returned when one of
+// the parts of the FEN-string can't be parsed; the others when the parts
+// are correct, but the position itself is not possible.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FenError {
+    NrOfParts,
+    Pieces,
+    Color,
+    Castling,
+    EnPassant,
+    HalfmoveClock,
+    FullmoveNumber,
+    KingCount,
+    PawnOnBackRank,
+    OpponentInCheck,
+    CastlingRights,
+    EnPassantSquare,
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            FenError::NrOfParts => "Must have six parts",
+            FenError::Pieces => "Pieces and squares incorrect",
+            FenError::Color => "Color selection incorrect",
+            FenError::Castling => "Castling permissions incorrect",
+            FenError::EnPassant => "En-passant square incorrect",
+            FenError::HalfmoveClock => "Half-move clock incorrect",
+            FenError::FullmoveNumber => "Full-move number incorrect",
+            FenError::KingCount => "Each side must have exactly one king",
+            FenError::PawnOnBackRank => "Pawns can't be on the first or eighth rank",
+            FenError::OpponentInCheck => "Side not to move is in check",
+            FenError::CastlingRights => "Castling permissions don't match king and rooks",
+            FenError::EnPassantSquare => "En-passant square not possible in this position",
+        };
+        write!(f, "FEN: {msg}")
+    }
+}
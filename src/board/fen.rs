@@ -21,20 +21,24 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-// fen.rs reads an FEN-string and converts it into a board position.
-// If the procedure fails, the original position is not changed. Note that
-// checking position legality is not the responsibility of this module. It
-// is perfectly possible to set up a position with two white kings, both
-// kings in check at the same time, or with black in check but white to
-// move.
+// fen.rs reads an FEN-string and converts it into a board position, and
+// writes the board position back as an FEN-string. If reading fails, the
+// original position is not changed. After parsing, the position is
+// validated: positions with the wrong number of kings, pawns on the back
+// ranks, the side not to move in check, castling permissions without a
+// king and rook to castle with (on their original squares in standard
+// chess), or an impossible en-passant square are rejected.
 
 use super::{
-    defs::{Files, Pieces, Ranks, Squares, BB_SQUARES},
+    defs::{
+        FenError, Files, Pieces, RangeOf, Ranks, Squares, Wing, BB_RANKS, BB_SQUARES, SQUARE_NAME,
+    },
     Board,
 };
 use crate::{
-    defs::{Castling, Sides, Square, FEN_START_POSITION, MAX_GAME_MOVES, MAX_MOVE_RULE},
+    defs::{NrOf, Side, Sides, Square, FEN_START_POSITION, MAX_GAME_MOVES, MAX_MOVE_RULE},
     misc::parse,
+    movegen::MoveGenerator,
 };
 use if_chain::if_chain;
 use std::ops::RangeInclusive;
@@ -46,18 +50,29 @@ const LIST_OF_PIECES: &str = "kqrbnpKQRBNP";
 const EP_SQUARES_WHITE: RangeInclusive<Square> = Squares::A3..=Squares::H3;
 const EP_SQUARES_BLACK: RangeInclusive<Square> = Squares::A6..=Squares::H6;
 const WHITE_OR_BLACK: &str = "wb";
-const CASTLING_RIGHTS: &str = "KQkq-";
+const CASTLING_RIGHTS: &str = "KQkqABCDEFGHabcdefgh-";
 const SPLITTER: char = '/';
 const DASH: char = '-';
 const EM_DASH: char = '–';
 const SPACE: char = ' ';
 
 type FenPartParser = fn(board: &mut Board, part: &str) -> bool;
-type FenResult = Result<(), u8>;
+type FenResult = Result<(), FenError>;
+
+// Error to return when parsing one of the FEN-parts fails.
+const FEN_PART_ERRORS: [FenError; NR_OF_FEN_PARTS] = [
+    FenError::Pieces,
+    FenError::Color,
+    FenError::Castling,
+    FenError::EnPassant,
+    FenError::HalfmoveClock,
+    FenError::FullmoveNumber,
+];
 
 impl Board {
     // This function reads a provided FEN-string or uses the default position.
-    pub fn fen_read(&mut self, fen_string: Option<&str>) -> FenResult {
+    // The move generator is needed to validate the position.
+    pub fn fen_read(&mut self, fen_string: Option<&str>, mg: &MoveGenerator) -> FenResult {
         // Split the string into parts. There should be 6 parts.
         let mut fen_parts: Vec<String> = match fen_string {
             Some(f) => f,
@@ -76,7 +91,11 @@ impl Board {
         let nr_of_parts_ok = fen_parts.len() == NR_OF_FEN_PARTS;
 
         // Set the initial result.
-        let mut result: FenResult = if nr_of_parts_ok { Ok(()) } else { Err(0) };
+        let mut result: FenResult = if nr_of_parts_ok {
+            Ok(())
+        } else {
+            Err(FenError::NrOfParts)
+        };
 
         if nr_of_parts_ok {
             // Create an array of function pointers; one parsing function per part.
@@ -92,19 +111,43 @@ impl Board {
                 let parser = &fen_parsers[i];
                 let part = &fen_parts[i];
                 let part_ok = parser(&mut new_board, part);
-                result = if part_ok { Ok(()) } else { Err(i as u8 + 1) };
+                result = if part_ok {
+                    Ok(())
+                } else {
+                    Err(FEN_PART_ERRORS[i])
+                };
                 i += 1;
             }
 
-            // Replace original board with new one if setup was successful.
+            // All parts are correct. Now see if the position is possible.
             if result == Ok(()) {
                 new_board.init();
+                result = validate(&new_board, mg);
+            }
+
+            // Replace original board with new one if setup was successful.
+            if result == Ok(()) {
                 *self = new_board;
             }
         }
 
         result
     }
+
+    // This function writes the current position as an FEN-string. Reading
+    // this string with fen_read() results in the same position.
+    pub fn fen_write(&self) -> String {
+        let fen_parts: [String; NR_OF_FEN_PARTS] = [
+            write_pieces(self),
+            write_color(self),
+            write_castling(self),
+            write_ep(self),
+            self.game_state.halfmove_clock.to_string(),
+            self.game_state.fullmove_number.to_string(),
+        ];
+
+        fen_parts.join(&SPACE.to_string())
+    }
 }
 
 // ===== Private functions =====
@@ -186,7 +229,10 @@ fn color(board: &mut Board, part: &str) -> bool {
     result
 }
 
-// Part 3: Parse castling rights.
+// Part 3: Parse castling rights. Next to the normal "KQkq", this also
+// accepts Shredder-FEN (the files of the castling rooks, such as "HAha")
+// and X-FEN ("KQkq", with a rook file only if the castling rook is not the
+// outermost rook on that wing). Both are used for Chess960.
 fn castling(board: &mut Board, part: &str) -> bool {
     let length = part.len();
     let mut char_ok = 0;
@@ -199,12 +245,8 @@ fn castling(board: &mut Board, part: &str) -> bool {
             if CASTLING_RIGHTS.contains(c) {
                 // Count correct characters
                 char_ok += 1;
-                match c {
-                    'K' => board.game_state.castling |= Castling::WK,
-                    'Q' => board.game_state.castling |= Castling::WQ,
-                    'k' => board.game_state.castling |= Castling::BK,
-                    'q' => board.game_state.castling |= Castling::BQ,
-                    _ => (),
+                if c != DASH {
+                    castling_right(board, c);
                 }
             }
         }
@@ -215,6 +257,50 @@ fn castling(board: &mut Board, part: &str) -> bool {
     (length >= 1) && (char_ok == length)
 }
 
+// Sets up the castling right for one character of the castling part. The
+// pieces have already been put on the board, so the king and rook squares
+// can be looked up. (If they're not where they should be, the squares of
+// normal chess are used. Position legality is not checked here.)
+fn castling_right(board: &mut Board, c: char) {
+    let side: Side = if c.is_ascii_uppercase() {
+        Sides::WHITE
+    } else {
+        Sides::BLACK
+    };
+    let rank = Board::back_rank(side) * 8;
+    let bb_rooks = board.bb_pieces[side][Pieces::ROOK];
+    let king = match board.king_square(side) {
+        k if Board::square_on_rank(k, Board::back_rank(side)) => k,
+        _ => rank + Files::E,
+    };
+    let king_file = king - rank;
+    let has_rook = |file: usize| bb_rooks & BB_SQUARES[rank + file] > 0;
+
+    // Find the file of the castling rook, and the wing it's on. For "K"
+    // and "Q", this is the outermost rook on that wing.
+    let (wing, file) = match c.to_ascii_uppercase() {
+        'K' => {
+            let rook = (king_file + 1..=Files::H).rev().find(|f| has_rook(*f));
+            (Wing::KING, rook.unwrap_or(Files::H))
+        }
+        'Q' => {
+            let rook = (Files::A..king_file).find(|f| has_rook(*f));
+            (Wing::QUEEN, rook.unwrap_or(Files::A))
+        }
+        f => {
+            let file = (f as u8 - b'A') as usize;
+            let wing = if file > king_file {
+                Wing::KING
+            } else {
+                Wing::QUEEN
+            };
+            (wing, file)
+        }
+    };
+
+    board.set_castling_rook(side, wing, rank + file, king);
+}
+
 // Part 4: Parse the en passant square
 fn ep(board: &mut Board, part: &str) -> bool {
     let length = part.len();
@@ -283,3 +369,199 @@ fn fmn(board: &mut Board, part: &str) -> bool {
 
     result
 }
+
+// ===== Private functions for validating the position =====
+
+// Checks if the position set up by the parsers is possible. The board has
+// been initialized, so all bitboards and the piece list can be used.
+fn validate(board: &Board, mg: &MoveGenerator) -> FenResult {
+    let us = board.us();
+    let opponent = board.opponent();
+    let bb_back_ranks = BB_RANKS[Ranks::R1] | BB_RANKS[Ranks::R8];
+    let bb_pawns =
+        board.bb_pieces[Sides::WHITE][Pieces::PAWN] | board.bb_pieces[Sides::BLACK][Pieces::PAWN];
+    let one_king = |side: Side| board.bb_pieces[side][Pieces::KING].count_ones() == 1;
+
+    if !one_king(Sides::WHITE) || !one_king(Sides::BLACK) {
+        return Err(FenError::KingCount);
+    }
+
+    if bb_pawns & bb_back_ranks > 0 {
+        return Err(FenError::PawnOnBackRank);
+    }
+
+    // The side that just moved can't have left its king in check.
+    if mg.square_attacked(board, us, board.king_square(opponent)) {
+        return Err(FenError::OpponentInCheck);
+    }
+
+    if !castling_rights_valid(board) {
+        return Err(FenError::CastlingRights);
+    }
+
+    if !ep_square_valid(board) {
+        return Err(FenError::EnPassantSquare);
+    }
+
+    Ok(())
+}
+
+// For each castling permission, the king must be on its back rank, with
+// the castling rook on the wing the permission is for. In standard chess,
+// the king and the rook must also still be on their original squares.
+fn castling_rights_valid(board: &Board) -> bool {
+    let mut valid = true;
+
+    for side in [Sides::WHITE, Sides::BLACK] {
+        for wing in [Wing::KING, Wing::QUEEN] {
+            if board.game_state.castling & Board::castling_right(side, wing) == 0 {
+                continue;
+            }
+
+            let king = board.king_square(side);
+            let rook = board.castling_rooks[side][wing];
+            let king_ok = Board::square_on_rank(king, Board::back_rank(side));
+            let rook_ok = board.bb_pieces[side][Pieces::ROOK] & BB_SQUARES[rook] > 0;
+            let wing_ok = Board::castling_wing(king, rook) == wing;
+
+            let rank = Board::back_rank(side) * 8;
+            let rook_file = if wing == Wing::KING {
+                Files::H
+            } else {
+                Files::A
+            };
+            let standard_ok =
+                board.chess960 || (king == rank + Files::E && rook == rank + rook_file);
+
+            valid = valid && king_ok && rook_ok && wing_ok && standard_ok;
+        }
+    }
+
+    valid
+}
+
+// An en-passant square can only exist directly after a double step. It
+// must be on the opponent's third rank, the square it's on and the square
+// the pawn came from must be empty, and the pawn must be in front of it.
+fn ep_square_valid(board: &Board) -> bool {
+    let ep = match board.game_state.en_passant {
+        Some(square) => square as usize,
+        None => return true,
+    };
+
+    let us = board.us();
+    let (rank, from, pawn) = if us == Sides::WHITE {
+        (Ranks::R6, ep + 8, ep - 8)
+    } else {
+        (Ranks::R3, ep - 8, ep + 8)
+    };
+    let bb_occupancy = board.occupancy();
+    let bb_opponent_pawns = board.bb_pieces[board.opponent()][Pieces::PAWN];
+
+    Board::square_on_rank(ep, rank)
+        && bb_occupancy & (BB_SQUARES[ep] | BB_SQUARES[from]) == 0
+        && bb_opponent_pawns & BB_SQUARES[pawn] > 0
+}
+
+// ===== Private functions for writing FEN =====
+
+// Part 1: Write the piece setup, from rank 8 down to rank 1. Empty squares
+// are counted, and the count is written when a piece or the end of the
+// rank is reached.
+fn write_pieces(board: &Board) -> String {
+    let mut part = String::new();
+    let pieces: Vec<char> = LIST_OF_PIECES.chars().collect();
+
+    for rank in RangeOf::RANKS.rev() {
+        let mut empty = 0;
+
+        for file in RangeOf::FILES {
+            let square = (rank * 8 + file) as usize;
+            let piece = board.piece_list[square];
+
+            if piece == Pieces::NONE {
+                empty += 1;
+                continue;
+            }
+
+            if empty > 0 {
+                part.push_str(&empty.to_string());
+                empty = 0;
+            }
+
+            // Black pieces come first in the list of pieces.
+            let is_white = board.bb_side[Sides::WHITE] & BB_SQUARES[square] > 0;
+            let offset = if is_white { NrOf::PIECE_TYPES } else { 0 };
+            part.push(pieces[piece + offset]);
+        }
+
+        if empty > 0 {
+            part.push_str(&empty.to_string());
+        }
+
+        if rank > Ranks::R1 as u8 {
+            part.push(SPLITTER);
+        }
+    }
+
+    part
+}
+
+// Part 2: Write the color to move.
+fn write_color(board: &Board) -> String {
+    let colors: Vec<char> = WHITE_OR_BLACK.chars().collect();
+    colors[board.us()].to_string()
+}
+
+// Part 3: Write the castling rights. This uses X-FEN: the castling rook
+// is written as "K" or "Q" if it's the outermost rook on its wing, and by
+// its file otherwise. For normal chess, this is just "KQkq".
+fn write_castling(board: &Board) -> String {
+    let mut part = String::new();
+
+    for side in [Sides::WHITE, Sides::BLACK] {
+        for wing in [Wing::KING, Wing::QUEEN] {
+            if board.game_state.castling & Board::castling_right(side, wing) == 0 {
+                continue;
+            }
+
+            let rank = Board::back_rank(side) * 8;
+            let rook_file = board.castling_rooks[side][wing] - rank;
+            let bb_rooks = board.bb_pieces[side][Pieces::ROOK];
+            let outer_files = if wing == Wing::KING {
+                (rook_file + 1)..(Files::H + 1)
+            } else {
+                Files::A..rook_file
+            };
+            let is_outermost = !outer_files
+                .into_iter()
+                .any(|f| bb_rooks & BB_SQUARES[rank + f] > 0);
+
+            let c = match (is_outermost, wing) {
+                (true, Wing::KING) => 'K',
+                (true, _) => 'Q',
+                (false, _) => (b'A' + rook_file as u8) as char,
+            };
+
+            part.push(if side == Sides::WHITE {
+                c
+            } else {
+                c.to_ascii_lowercase()
+            });
+        }
+    }
+
+    if part.is_empty() {
+        part.push(DASH);
+    }
+
+    part
+}
+
+// Part 4: Write the en passant square.
+fn write_ep(board: &Board) -> String {
+    match board.game_state.en_passant {
+        Some(square) => SQUARE_NAME[square as usize].to_string(),
+        None => DASH.to_string(),
+    }
+}
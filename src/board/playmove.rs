@@ -24,35 +24,18 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 // playmove.rs contains make() and unamke() for move execution and reversal.
 
 use super::{
-    defs::{Pieces, Squares, BB_SQUARES},
+    defs::{Pieces, BB_SQUARES},
     Board,
 };
 use crate::{
-    defs::{Castling, NrOf, Piece, Side, Sides, Square},
+    defs::{Piece, Side, Sides, Square},
     movegen::{defs::Move, MoveGenerator},
 };
 
-// Castling Permissions Per Square
-type CPSquare = [u8; NrOf::SQUARES];
-const CASTLING_PERMS: CPSquare = castling_permissions_per_square();
-const fn castling_permissions_per_square() -> CPSquare {
-    // First set all squares grant all castling permissions. This means
-    // moving a piece on such square doesn't have any effect on castling
-    // permissions.
-    let mut cp: CPSquare = [Castling::ALL; NrOf::SQUARES];
-
-    // Now disable castling permissions when moving pieces on certain
-    // squares. For example, when the piece (rook) on A1 moves, disable
-    // white castling to the queenside.
-    cp[Squares::A1] &= !Castling::WQ;
-    cp[Squares::E1] &= !Castling::WK & !Castling::WQ;
-    cp[Squares::H1] &= !Castling::WK;
-    cp[Squares::A8] &= !Castling::BQ;
-    cp[Squares::E8] &= !Castling::BK & !Castling::BQ;
-    cp[Squares::H8] &= !Castling::BK;
-
-    cp
-}
+// Castling permissions are removed through the board's castling mask. It
+// holds, per square, the permissions that remain when a piece moves from
+// or to that square. It is set up by the FEN-reader, because in Chess960
+// the king and rooks can start on any square of the back rank.
 
 /*** ================================================================================ ***/
 
@@ -101,12 +84,15 @@ impl Board {
             self.game_state.halfmove_clock = 0;
             // Change castling permissions on rook capture in the corner.
             if captured == Pieces::ROOK && has_permissions {
-                self.update_castling_permissions(self.game_state.castling & CASTLING_PERMS[to]);
+                self.update_castling_permissions(self.game_state.castling & self.castling_mask[to]);
             }
         }
 
-        // Make the move. Just move the piece if it's not a pawn.
-        if piece != Pieces::PAWN {
+        // Make the move. Castling moves the king and the rook. Any other
+        // piece that is not a pawn is just moved.
+        if castling {
+            self.castle(us, from, to);
+        } else if piece != Pieces::PAWN {
             self.move_piece(us, piece, from, to);
         } else {
             // It's a pawn move. Take promotion into account and reset halfmove_clock.
@@ -128,18 +114,7 @@ impl Board {
         // Remove castling permissions if king/rook leaves from starting square.
         // (This will also adjust permissions when castling, because the king moves.)
         if (piece == Pieces::KING || piece == Pieces::ROOK) && has_permissions {
-            self.update_castling_permissions(self.game_state.castling & CASTLING_PERMS[from]);
-        }
-
-        // If the king is castling, then also move the rook.
-        if castling {
-            match to {
-                Squares::G1 => self.move_piece(us, Pieces::ROOK, Squares::H1, Squares::F1),
-                Squares::C1 => self.move_piece(us, Pieces::ROOK, Squares::A1, Squares::D1),
-                Squares::G8 => self.move_piece(us, Pieces::ROOK, Squares::H8, Squares::F8),
-                Squares::C8 => self.move_piece(us, Pieces::ROOK, Squares::A8, Squares::D8),
-                _ => panic!("Error moving rook during castling."),
-            }
+            self.update_castling_permissions(self.game_state.castling & self.castling_mask[from]);
         }
 
         // Swap the side to move.
@@ -163,6 +138,19 @@ impl Board {
         // Report if the move was legal or not.
         is_legal
     }
+
+    // Castling moves are encoded as "king takes rook". Both pieces are
+    // taken off the board first, because in Chess960 the king can end up
+    // on the rook's square, or the other way around.
+    fn castle(&mut self, us: Side, king: Square, rook: Square) {
+        let wing = Board::castling_wing(king, rook);
+        let (king_to, rook_to) = Board::castling_destinations(us, wing);
+
+        self.remove_piece(us, Pieces::KING, king);
+        self.remove_piece(us, Pieces::ROOK, rook);
+        self.put_piece(us, Pieces::KING, king_to);
+        self.put_piece(us, Pieces::ROOK, rook_to);
+    }
 }
 
 /*** ================================================================================ ***/
@@ -189,26 +177,24 @@ impl Board {
         let castling = m.castling();
         let en_passant = m.en_passant();
 
-        // Moving backwards...
-        if promoted == Pieces::NONE {
+        // Moving backwards... When castling, both the king and the rook
+        // are taken off their destination squares before putting them
+        // back, as these may overlap with the start squares.
+        if castling {
+            let wing = Board::castling_wing(from, to);
+            let (king_to, rook_to) = Board::castling_destinations(us, wing);
+
+            remove_piece(self, us, Pieces::KING, king_to);
+            remove_piece(self, us, Pieces::ROOK, rook_to);
+            put_piece(self, us, Pieces::KING, from);
+            put_piece(self, us, Pieces::ROOK, to);
+        } else if promoted == Pieces::NONE {
             reverse_move(self, us, piece, to, from);
         } else {
             remove_piece(self, us, promoted, to);
             put_piece(self, us, Pieces::PAWN, from);
         }
 
-        // The king's move was already undone as a normal move.
-        // Now undo the correct castling rook move.
-        if castling {
-            match to {
-                Squares::G1 => reverse_move(self, us, Pieces::ROOK, Squares::F1, Squares::H1),
-                Squares::C1 => reverse_move(self, us, Pieces::ROOK, Squares::D1, Squares::A1),
-                Squares::G8 => reverse_move(self, us, Pieces::ROOK, Squares::F8, Squares::H8),
-                Squares::C8 => reverse_move(self, us, Pieces::ROOK, Squares::D8, Squares::A8),
-                _ => panic!("Error: Reversing castling rook move."),
-            };
-        }
-
         // If a piece was captured, put it back onto the to-square
         if captured != Pieces::NONE {
             put_piece(self, opponent, captured, to);
@@ -221,6 +207,49 @@ impl Board {
     }
 }
 
+/*** ================================================================================ ***/
+
+// A null move passes the turn to the opponent without moving a piece.
+// It's not a legal chess move; the search uses it to find out if a
+// position is so strong that even passing doesn't spoil it. The null move
+// is stored in the history as a move without any data.
+impl Board {
+    pub fn make_null(&mut self) {
+        let mut current_game_state = self.game_state;
+        current_game_state.next_move = Move::new(0);
+        self.history.push(current_game_state);
+
+        // Passing the turn loses the right to capture en-passant.
+        if self.game_state.en_passant.is_some() {
+            self.clear_ep_square();
+        }
+
+        // A position before the null move can't be repeated after it, as
+        // the side to move would be different. Resetting the halfmove
+        // clock stops repetition detection at the null move.
+        self.game_state.halfmove_clock = 0;
+
+        if self.us() == Sides::BLACK {
+            self.game_state.fullmove_number += 1;
+        }
+
+        self.swap_side();
+
+        debug_assert!(check_incrementals(self));
+    }
+
+    // Nothing was moved, so restoring the game state is enough.
+    pub fn unmake_null(&mut self) {
+        self.game_state = self.history.pop();
+    }
+
+    // Returns true if the last move played was a null move.
+    pub fn last_move_is_null(&self) -> bool {
+        let len = self.history.len();
+        len > 0 && self.history.get_ref(len - 1).next_move.get_move() == 0
+    }
+}
+
 /*** Functions local to playmove.rs ====================================================== ***/
 
 // unamke() pops the entire game history from a list at the beginning. This
@@ -21,10 +21,13 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-use super::{defs::Location, Board};
+use super::{
+    defs::{Files, Location, Wing},
+    Board,
+};
 use crate::{
     board::defs::Ranks,
-    defs::{Side, Sides, Square},
+    defs::{Castling, Side, Sides, Square},
 };
 
 impl Board {
@@ -57,4 +60,37 @@ impl Board {
             Ranks::R1
         }
     }
+
+    pub fn back_rank(side: Side) -> usize {
+        if side == Sides::WHITE {
+            Ranks::R1
+        } else {
+            Ranks::R8
+        }
+    }
+
+    // Returns the castling permission for the given side and wing.
+    pub fn castling_right(side: Side, wing: usize) -> u8 {
+        Castling::WK << (side * 2 + wing)
+    }
+
+    // Returns the wing a castling rook is on, as seen from the king.
+    pub fn castling_wing(king: Square, rook: Square) -> usize {
+        if rook > king {
+            Wing::KING
+        } else {
+            Wing::QUEEN
+        }
+    }
+
+    // Returns the squares the king and the rook end up on after castling.
+    // These are the same in normal chess and Chess960.
+    pub fn castling_destinations(side: Side, wing: usize) -> (Square, Square) {
+        let rank = Board::back_rank(side) * 8;
+        if wing == Wing::KING {
+            (rank + Files::G, rank + Files::F)
+        } else {
+            (rank + Files::C, rank + Files::D)
+        }
+    }
 }
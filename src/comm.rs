@@ -22,17 +22,21 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 pub mod uci;
-// pub mod xboard;
+pub mod xboard;
 
 use crate::{
     board::Board,
     engine::defs::{EngineOption, Information},
     movegen::defs::Move,
-    search::defs::{SearchCurrentMove, SearchStats, SearchSummary},
+    search::{
+        defs::{BenchResult, SearchCurrentMove, SearchStats, SearchSummary},
+        BENCH_DEPTH,
+    },
 };
 use crossbeam_channel::Sender;
 use std::sync::{Arc, Mutex};
 use uci::UciReport;
+use xboard::XboardReport;
 
 // These are the types of communication the engine is capable of.
 pub struct CommType;
@@ -65,11 +69,18 @@ pub enum CommControl {
     SearchCurrMove(SearchCurrentMove), // Transmit currently considered move.
     SearchStats(SearchStats),          // Transmit search Statistics.
     InfoString(String),                // Transmit general information.
-    BestMove(Move),                    // Transmit the engine's best move.
+    BestMove(Move, Option<Move>),      // Transmit best move and ponder move.
+    Pong(isize),                       // Answer a ping from the GUI.
+    IllegalMove(String),               // Transmit that a move was illegal.
+    Error(String, String),             // Transmit error type and command.
 
     // Output to screen when running in a terminal window.
     PrintBoard,
-    PrintHistory,
+    PrintHistory(Vec<String>),
+    PrintFen(String),
+    PrintPgn(String),
+    PrintDivide(Vec<(Move, u64)>, u128),
+    PrintBench(BenchResult),
     PrintHelp,
 }
 
@@ -78,6 +89,8 @@ pub enum CommControl {
 #[derive(PartialEq, Clone)]
 pub enum CommReport {
     Uci(UciReport),
+    Xboard(XboardReport),
+    Custom(CustomReport),
 }
 
 impl CommReport {
@@ -85,3 +98,43 @@ impl CommReport {
         true
     }
 }
+
+// These are custom commands, which are not part of any protocol. They are
+// available in every Comm module, to make use through a terminal window
+// easier.
+#[derive(PartialEq, Clone)]
+pub enum CustomReport {
+    Board,
+    History,
+    Fen,
+    Pgn,
+    Perft(i8),
+    Bench(i8),
+    Eval,
+    Help,
+}
+
+impl CustomReport {
+    // Turns the input into a custom command. Returns None if the input is
+    // not a custom command, or if its depth is invalid.
+    pub fn parse(cmd: &str) -> Option<CustomReport> {
+        let mut parts = cmd.split_whitespace();
+        let command = parts.next().unwrap_or("");
+        let depth = parts
+            .next()
+            .map(|d| d.parse::<i8>().ok().filter(|&d| d > 0));
+
+        match (command, depth) {
+            ("board", None) => Some(CustomReport::Board),
+            ("history", None) => Some(CustomReport::History),
+            ("fen", None) => Some(CustomReport::Fen),
+            ("pgn", None) => Some(CustomReport::Pgn),
+            ("perft", Some(Some(d))) => Some(CustomReport::Perft(d)),
+            ("bench", None) => Some(CustomReport::Bench(BENCH_DEPTH)),
+            ("bench", Some(Some(d))) => Some(CustomReport::Bench(d)),
+            ("eval", None) => Some(CustomReport::Eval),
+            ("help", None) => Some(CustomReport::Help),
+            _ => None,
+        }
+    }
+}
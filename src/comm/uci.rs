@@ -23,7 +23,7 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 // This file implements the UCI communication module.
 
-use super::{CommControl, CommReport, CommType, IComm};
+use super::{CommControl, CommReport, CommType, CustomReport, IComm};
 use crate::{
     board::Board,
     defs::{About, FEN_START_POSITION},
@@ -31,12 +31,13 @@ use crate::{
     misc::print,
     movegen::defs::Move,
     search::defs::{
-        GameTime, SearchCurrentMove, SearchStats, SearchSummary, CHECKMATE, CHECKMATE_THRESHOLD,
+        GameTime, ScoreBound, SearchCurrentMove, SearchParams, SearchStats, SearchSummary,
+        CHECKMATE, CHECKMATE_THRESHOLD,
     },
 };
 use crossbeam_channel::{self, Sender};
 use std::{
-    io::{self},
+    io::{self, IsTerminal},
     sync::{Arc, Mutex},
     thread::{self, JoinHandle},
 };
@@ -51,20 +52,11 @@ pub enum UciReport {
     IsReady,
     SetOption(EngineOptionName),
     Position(String, Vec<String>),
-    GoInfinite,
-    GoDepth(i8),
-    GoMoveTime(u128),
-    GoNodes(usize),
-    GoGameTime(GameTime),
+    Go(SearchParams, Vec<String>),
+    PonderHit,
     Stop,
     Quit,
 
-    // Custom commands
-    Board,
-    History,
-    Eval,
-    Help,
-
     // Empty or unknown command.
     Unknown,
 }
@@ -184,10 +176,17 @@ impl Uci {
             let t_board = Arc::clone(&board);
             let t_options = Arc::clone(&options);
 
+            // Someone using the engine in a terminal window gets to see
+            // the PV in SAN. GUIs are always connected through pipes.
+            let console = io::stdin().is_terminal();
+
             // Keep running as long as Quit is not received.
             while !quit {
                 let control = control_rx.recv().expect(ErrFatal::CHANNEL);
 
+                // Castling moves are written differently in Chess960.
+                let chess960 = t_board.lock().expect(ErrFatal::LOCK).chess960;
+
                 // Perform command as sent by the engine thread.
                 match control {
                     CommControl::Identify => {
@@ -197,19 +196,34 @@ impl Uci {
                     }
                     CommControl::Ready => Uci::readyok(),
                     CommControl::Quit => quit = true,
-                    CommControl::SearchSummary(summary) => Uci::search_summary(&summary),
-                    CommControl::SearchCurrMove(current) => Uci::search_currmove(&current),
+                    CommControl::SearchSummary(summary) => {
+                        Uci::search_summary(&summary, chess960, console)
+                    }
+                    CommControl::SearchCurrMove(current) => {
+                        Uci::search_currmove(&current, chess960)
+                    }
                     CommControl::SearchStats(stats) => Uci::search_stats(&stats),
                     CommControl::InfoString(msg) => Uci::info_string(&msg),
-                    CommControl::BestMove(bm) => Uci::best_move(&bm),
+                    CommControl::BestMove(bm, pm) => Uci::best_move(&bm, pm, chess960),
 
                     // Custom prints for use in the console.
-                    CommControl::PrintBoard => Uci::print_board(&t_board),
-                    CommControl::PrintHistory => Uci::print_history(&t_board),
-                    CommControl::PrintHelp => Uci::print_help(),
+                    CommControl::PrintBoard => {
+                        print::position(&t_board.lock().expect(ErrFatal::LOCK), None)
+                    }
+                    CommControl::PrintHistory(history) => print::history(&history),
+                    CommControl::PrintFen(fen) => print::fen(&fen),
+                    CommControl::PrintPgn(pgn) => print::pgn(&pgn),
+                    CommControl::PrintDivide(result, elapsed) => {
+                        print::divide(&result, elapsed, chess960)
+                    }
+                    CommControl::PrintBench(result) => print::bench(&result),
+                    CommControl::PrintHelp => print::help("UCI"),
 
                     // Comm Control commands that are not (yet) used.
-                    CommControl::Update => (),
+                    CommControl::Update
+                    | CommControl::Pong(_)
+                    | CommControl::IllegalMove(_)
+                    | CommControl::Error(_, _) => (),
                 }
             }
         });
@@ -228,6 +242,11 @@ impl Uci {
         // Trim CR/LF so only the usable characters remain.
         let i = input.trim_end().to_string();
 
+        // Custom commands are the same for every protocol.
+        if let Some(custom) = CustomReport::parse(&i) {
+            return CommReport::Custom(custom);
+        }
+
         // Convert to &str for matching the command.
         match i {
             // UCI commands
@@ -235,17 +254,13 @@ impl Uci {
             cmd if cmd == "ucinewgame" => CommReport::Uci(UciReport::UciNewGame),
             cmd if cmd == "isready" => CommReport::Uci(UciReport::IsReady),
             cmd if cmd == "stop" => CommReport::Uci(UciReport::Stop),
+            cmd if cmd == "ponderhit" => CommReport::Uci(UciReport::PonderHit),
             cmd if cmd == "quit" || cmd == "exit" => CommReport::Uci(UciReport::Quit),
             cmd if cmd.starts_with("setoption") => Uci::parse_setoption(&cmd),
             cmd if cmd.starts_with("position") => Uci::parse_position(&cmd),
+            cmd if cmd.starts_with("go perft") => Uci::parse_perft(&cmd),
             cmd if cmd.starts_with("go") => Uci::parse_go(&cmd),
 
-            // Custom commands
-            cmd if cmd == "board" => CommReport::Uci(UciReport::Board),
-            cmd if cmd == "history" => CommReport::Uci(UciReport::History),
-            cmd if cmd == "eval" => CommReport::Uci(UciReport::Eval),
-            cmd if cmd == "help" => CommReport::Uci(UciReport::Help),
-
             // Everything else is ignored.
             _ => CommReport::Uci(UciReport::Unknown),
         }
@@ -298,42 +313,43 @@ impl Uci {
             WInc,
             BInc,
             MovesToGo,
+            SearchMoves,
+            Mate,
         }
 
         let parts: Vec<String> = cmd.split_whitespace().map(|s| s.to_string()).collect();
-        let mut report = CommReport::Uci(UciReport::Unknown);
+        let mut sp = SearchParams::new();
         let mut token = Tokens::Nothing;
         let mut game_time = GameTime::new(0, 0, 0, 0, None);
+        let mut search_moves: Vec<String> = Vec::new();
+
+        // The game time is only used if the GUI actually sent a clock.
+        let clock = ["wtime", "btime", "winc", "binc"];
+        let has_clock = parts.iter().any(|p| clock.contains(&p.as_str()));
 
+        // Every limit is collected, so they can be combined. The search
+        // stops at whichever limit is reached first.
         for p in parts {
             match p {
-                t if t == "go" => report = CommReport::Uci(UciReport::GoInfinite),
-                t if t == "infinite" => break, // Already Infinite; nothing more to do.
+                t if t == "go" => (),
+                t if t == "infinite" => sp.infinite = true,
+                t if t == "ponder" => sp.ponder = true,
                 t if t == "depth" => token = Tokens::Depth,
                 t if t == "movetime" => token = Tokens::MoveTime,
                 t if t == "nodes" => token = Tokens::Nodes,
+                t if t == "mate" => token = Tokens::Mate,
                 t if t == "wtime" => token = Tokens::WTime,
                 t if t == "btime" => token = Tokens::BTime,
                 t if t == "winc" => token = Tokens::WInc,
                 t if t == "binc" => token = Tokens::BInc,
                 t if t == "movestogo" => token = Tokens::MovesToGo,
+                t if t == "searchmoves" => token = Tokens::SearchMoves,
                 _ => match token {
                     Tokens::Nothing => (),
-                    Tokens::Depth => {
-                        let depth = p.parse::<i8>().unwrap_or(1);
-                        report = CommReport::Uci(UciReport::GoDepth(depth));
-                        break; // break for-loop: nothing more to do.
-                    }
-                    Tokens::MoveTime => {
-                        let milliseconds = p.parse::<u128>().unwrap_or(1000);
-                        report = CommReport::Uci(UciReport::GoMoveTime(milliseconds));
-                        break; // break for-loop: nothing more to do.
-                    }
-                    Tokens::Nodes => {
-                        let nodes = p.parse::<usize>().unwrap_or(1);
-                        report = CommReport::Uci(UciReport::GoNodes(nodes));
-                        break; // break for-loop: nothing more to do.
-                    }
+                    Tokens::Depth => sp.depth = Some(p.parse::<i8>().unwrap_or(1)),
+                    Tokens::MoveTime => sp.move_time = Some(p.parse::<u128>().unwrap_or(1000)),
+                    Tokens::Nodes => sp.nodes = Some(p.parse::<usize>().unwrap_or(1)),
+                    Tokens::Mate => sp.mate = Some(p.parse::<u8>().unwrap_or(1)),
                     Tokens::WTime => game_time.wtime = p.parse::<u128>().unwrap_or(0),
                     Tokens::BTime => game_time.btime = p.parse::<u128>().unwrap_or(0),
                     Tokens::WInc => game_time.winc = p.parse::<u128>().unwrap_or(0),
@@ -345,24 +361,36 @@ impl Uci {
                             None
                         }
                     }
+                    Tokens::SearchMoves => search_moves.push(p),
                 }, // end match token
             } // end match p
         } // end for
 
-        // If we are still in the default "go infinite" mode, we must
-        // switch to GameTime mode if at least one parameter of "go wtime
-        // btime winc binc" was set to something else but 0.
-        let is_default_mode = report == CommReport::Uci(UciReport::GoInfinite);
-        let has_time = game_time.wtime > 0 || game_time.btime > 0;
-        let has_inc = game_time.winc > 0 || game_time.binc > 0;
-        let is_game_time = has_time || has_inc;
-        if is_default_mode && is_game_time {
-            report = CommReport::Uci(UciReport::GoGameTime(game_time));
+        if has_clock {
+            sp.game_time = Some(game_time);
+        }
+
+        // Without any limits, the search runs until it is told to stop.
+        let has_limits = sp.depth.is_some()
+            || sp.move_time.is_some()
+            || sp.nodes.is_some()
+            || sp.mate.is_some()
+            || sp.game_time.is_some();
+        if !has_limits {
+            sp.infinite = true;
         }
 
-        report
+        CommReport::Uci(UciReport::Go(sp, search_moves))
     } // end parse_go()
 
+    // Parses "go perft <depth>" as the custom "perft <depth>" command.
+    fn parse_perft(cmd: &str) -> CommReport {
+        match CustomReport::parse(cmd.trim_start_matches("go")) {
+            Some(custom @ CustomReport::Perft(_)) => CommReport::Custom(custom),
+            _ => CommReport::Uci(UciReport::Unknown),
+        }
+    }
+
     fn parse_setoption(cmd: &str) -> CommReport {
         enum Tokens {
             Nothing,
@@ -395,6 +423,11 @@ impl Uci {
             match &name[..] {
                 "hash" => eon = EngineOptionName::Hash(value),
                 "clear hash" => eon = EngineOptionName::ClearHash,
+                "threads" => eon = EngineOptionName::Threads(value),
+                "multipv" => eon = EngineOptionName::MultiPv(value),
+                "ponder" => eon = EngineOptionName::Ponder(value),
+                "uci_chess960" => eon = EngineOptionName::Chess960(value),
+                "nullmoveverification" => eon = EngineOptionName::NullMoveVerification(value),
                 _ => (),
             }
         }
@@ -418,6 +451,7 @@ impl Uci {
             let ui_element = match o.ui_element {
                 UiElement::Spin => String::from("type spin"),
                 UiElement::Button => String::from("type button"),
+                UiElement::Check => String::from("type check"),
             };
 
             let value_default = if let Some(v) = &o.default {
@@ -456,7 +490,7 @@ impl Uci {
         println!("readyok");
     }
 
-    fn search_summary(s: &SearchSummary) {
+    fn search_summary(s: &SearchSummary, chess960: bool, console: bool) {
         // If mate found, report this; otherwise report normal score.
         let score = if (s.cp.abs() >= CHECKMATE_THRESHOLD) && (s.cp.abs() < CHECKMATE) {
             // Number of plies to mate.
@@ -492,20 +526,52 @@ impl Uci {
             String::from(" ")
         };
 
-        let pv = s.pv_as_string();
+        // Only display the line number in MultiPV mode.
+        let multi_pv = if s.multi_pv > 0 {
+            format!("multipv {} ", s.multi_pv)
+        } else {
+            String::from("")
+        };
+
+        let pv = if console {
+            s.pv_as_san()
+        } else {
+            s.pv_as_string(chess960)
+        };
+
+        // Scores outside the aspiration window are bounds.
+        let score = match s.bound {
+            ScoreBound::Exact => score,
+            ScoreBound::Lower => format!("{score} lowerbound"),
+            ScoreBound::Upper => format!("{score} upperbound"),
+        };
+
+        // A bound comes without a PV.
+        let pv = if pv.is_empty() {
+            String::from("")
+        } else {
+            format!(" pv {pv}")
+        };
 
         let info = format!(
-            "info score {} {} time {} nodes {} nps {}{}pv {}",
-            score, depth, s.time, s.nodes, s.nps, hash_full, pv,
+            "info {}score {} {} time {} nodes {} nps {}{}{}",
+            multi_pv,
+            score,
+            depth,
+            s.time,
+            s.nodes,
+            s.nps,
+            hash_full.trim_end(),
+            pv,
         );
 
         println!("{info}");
     }
 
-    fn search_currmove(c: &SearchCurrentMove) {
+    fn search_currmove(c: &SearchCurrentMove, chess960: bool) {
         println!(
             "info currmove {} currmovenumber {}",
-            c.curr_move.as_string(),
+            c.curr_move.as_string(chess960),
             c.curr_move_number
         );
     }
@@ -527,46 +593,12 @@ impl Uci {
         println!("info string {msg}");
     }
 
-    fn best_move(m: &Move) {
-        println!("bestmove {}", m.as_string());
-    }
-}
-
-// implements handling of custom commands. These are mostly used when using
-// the UCI protocol directly in a terminal window.
-impl Uci {
-    fn print_board(board: &Arc<Mutex<Board>>) {
-        print::position(&board.lock().expect(ErrFatal::LOCK), None);
-    }
-
-    fn print_history(board: &Arc<Mutex<Board>>) {
-        let mtx_board = board.lock().expect(ErrFatal::LOCK);
-        let length = mtx_board.history.len();
-
-        if length == 0 {
-            println!("No history available.");
-        }
-
-        for i in 0..length {
-            let h = mtx_board.history.get_ref(i);
-            println!("{:<3}| ply: {} {}", i, i + 1, h.as_string());
+    fn best_move(m: &Move, ponder: Option<Move>, chess960: bool) {
+        if let Some(p) = ponder {
+            let (m, p) = (m.as_string(chess960), p.as_string(chess960));
+            println!("bestmove {m} ponder {p}");
+        } else {
+            println!("bestmove {}", m.as_string(chess960));
         }
-
-        std::mem::drop(mtx_board);
-    }
-
-    fn print_help() {
-        println!("The engine is in UCI communication mode. It supports some custom");
-        println!("non-UCI commands to make use through a terminal window easier.");
-        println!("These commands can also be very useful for debugging purposes.");
-        println!();
-        println!("Custom commands");
-        println!("================================================================");
-        println!("help      :   This help information.");
-        println!("board     :   Print the current board state.");
-        println!("history   :   Print a list of past board states.");
-        println!("eval      :   Print evaluation for side to move.");
-        println!("exit      :   Quit/Exit the engine.");
-        println!();
     }
 }
@@ -21,19 +21,447 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-use super::IComm;
-// use crate::{board::Board, movegen::MoveGenerator};
+// This file implements the XBoard (CECP) communication module.
 
-pub struct Xboard;
+use super::{CommControl, CommReport, CommType, CustomReport, IComm};
+use crate::{
+    board::Board,
+    defs::About,
+    engine::defs::{EngineOption, ErrFatal, Information},
+    misc::{parse, print},
+    movegen::defs::Move,
+    search::defs::{ScoreBound, SearchSummary, CHECKMATE, CHECKMATE_THRESHOLD},
+};
+use crossbeam_channel::{self, Sender};
+use std::{
+    io::{self, IsTerminal},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+};
 
+// XBoard reports mate scores as 100000 + the number of moves to mate.
+const MATE_SCORE: i32 = 100_000;
+
+// Input will be turned into a report, which wil be sent to the engine. The
+// main engine thread will react accordingly.
+#[derive(PartialEq, Clone)]
+pub enum XboardReport {
+    // XBoard commands
+    Xboard,
+    ProtoVer(u8),
+    New,
+    Force,
+    Go,
+    PlayOther,
+    UserMove(String),
+    SetBoard(String),
+    Level(usize, u128, u128),
+    St(u128),
+    Sd(i8),
+    Time(u128),
+    OTim(u128),
+    Undo,
+    Remove,
+    Ping(isize),
+    Post,
+    NoPost,
+    MoveNow,
+    Memory(usize),
+    Result,
+    Quit,
+
+    // Commands that are known, but ignored by the engine.
+    Ignore,
+
+    // Unknown command.
+    Unknown(String),
+}
+
+// This struct is used to instantiate the Comm XBoard module.
+pub struct Xboard {
+    control_handle: Option<JoinHandle<()>>,
+    report_handle: Option<JoinHandle<()>>,
+    control_tx: Option<Sender<CommControl>>,
+}
+
+// Public functions
 impl Xboard {
+    // Create a new XBoard module.
     pub fn new() -> Self {
-        Self {}
+        Self {
+            control_handle: None,
+            report_handle: None,
+            control_tx: None,
+        }
     }
 }
 
+// Any communication module must implement the trait IComm.
 impl IComm for Xboard {
-    fn start(&self) {
-        println!("XBoard communication.");
+    fn init(
+        &mut self,
+        report_tx: Sender<Information>,
+        board: Arc<Mutex<Board>>,
+        options: Arc<Vec<EngineOption>>,
+    ) {
+        // Start threads
+        self.report_thread(report_tx);
+        self.control_thread(board, options);
+    }
+
+    // The creator of the Comm module can use this function to send
+    // messages or commands into the Control thread.
+    fn send(&self, msg: CommControl) {
+        if let Some(tx) = &self.control_tx {
+            tx.send(msg).expect(ErrFatal::CHANNEL);
+        }
+    }
+
+    // After the engine sends 'quit' to the control thread, it will call
+    // wait_for_shutdown() and then wait here until shutdown is completed.
+    fn wait_for_shutdown(&mut self) {
+        if let Some(h) = self.report_handle.take() {
+            h.join().expect(ErrFatal::THREAD);
+        }
+
+        if let Some(h) = self.control_handle.take() {
+            h.join().expect(ErrFatal::THREAD);
+        }
+    }
+
+    // This function just returns the name of the communication protocol.
+    fn get_protocol_name(&self) -> &'static str {
+        CommType::XBOARD
+    }
+}
+
+// Implement the report thread
+impl Xboard {
+    // The Report thread sends incoming data to the engine thread.
+    fn report_thread(&mut self, report_tx: Sender<Information>) {
+        // Create thread-local variables
+        let mut t_incoming_data = String::from("");
+        let t_report_tx = report_tx; // Report sender
+
+        // Actual thread creation.
+        let report_handle = thread::spawn(move || {
+            let mut quit = false;
+
+            // Keep running as long as 'quit' is not detected.
+            while !quit {
+                // Get data from stdin.
+                io::stdin()
+                    .read_line(&mut t_incoming_data)
+                    .expect(ErrFatal::READ_IO);
+
+                // Create a report from the incoming data.
+                let new_report = Xboard::create_report(&t_incoming_data);
+
+                // Check if the created report is valid, so it is something
+                // the engine will understand.
+                if new_report.is_valid() {
+                    // Send it to the engine thread.
+                    t_report_tx
+                        .send(Information::Comm(new_report.clone()))
+                        .expect(ErrFatal::HANDLE);
+
+                    // Terminate the reporting thread if "Quit" was detected.
+                    quit = new_report == CommReport::Xboard(XboardReport::Quit);
+                }
+
+                // Clear for next input
+                t_incoming_data = String::from("");
+            }
+        });
+
+        // Store the handle.
+        self.report_handle = Some(report_handle);
+    }
+}
+
+// Implement the control thread
+impl Xboard {
+    // The control thread receives commands from the engine thread.
+    fn control_thread(&mut self, board: Arc<Mutex<Board>>, _options: Arc<Vec<EngineOption>>) {
+        // Create an incoming channel for the control thread.
+        let (control_tx, control_rx) = crossbeam_channel::unbounded::<CommControl>();
+
+        // Create the control thread.
+        let control_handle = thread::spawn(move || {
+            let mut quit = false;
+            let t_board = Arc::clone(&board);
+
+            // Someone using the engine in a terminal window gets to see
+            // the PV in SAN. GUIs are always connected through pipes.
+            let console = io::stdin().is_terminal();
+
+            // Keep running as long as Quit is not received.
+            while !quit {
+                let control = control_rx.recv().expect(ErrFatal::CHANNEL);
+
+                // Castling moves are written differently in Chess960.
+                let chess960 = t_board.lock().expect(ErrFatal::LOCK).chess960;
+
+                // Perform command as sent by the engine thread.
+                match control {
+                    CommControl::Identify => Xboard::features(),
+                    CommControl::Quit => quit = true,
+                    CommControl::SearchSummary(summary) => {
+                        Xboard::search_summary(&summary, chess960, console)
+                    }
+                    CommControl::InfoString(msg) => Xboard::info_string(&msg),
+                    CommControl::BestMove(bm, _) => Xboard::best_move(&bm, chess960),
+                    CommControl::Pong(n) => Xboard::pong(n),
+                    CommControl::IllegalMove(m) => Xboard::illegal_move(&m),
+                    CommControl::Error(e, cmd) => Xboard::error(&e, &cmd),
+
+                    // Custom prints for use in the console.
+                    CommControl::PrintBoard => {
+                        print::position(&t_board.lock().expect(ErrFatal::LOCK), None)
+                    }
+                    CommControl::PrintHistory(history) => print::history(&history),
+                    CommControl::PrintFen(fen) => print::fen(&fen),
+                    CommControl::PrintPgn(pgn) => print::pgn(&pgn),
+                    CommControl::PrintDivide(result, elapsed) => {
+                        print::divide(&result, elapsed, chess960)
+                    }
+                    CommControl::PrintBench(result) => print::bench(&result),
+                    CommControl::PrintHelp => print::help("XBoard"),
+
+                    // Comm Control commands that are not used by XBoard.
+                    CommControl::Update
+                    | CommControl::Ready
+                    | CommControl::SearchCurrMove(_)
+                    | CommControl::SearchStats(_) => (),
+                }
+            }
+        });
+
+        // Store handle and control sender.
+        self.control_handle = Some(control_handle);
+        self.control_tx = Some(control_tx);
+    }
+}
+
+// Private functions for this module.
+impl Xboard {
+    // This function turns the incoming data into XBoardReports which the
+    // engine is able to understand and react to.
+    fn create_report(input: &str) -> CommReport {
+        // Trim CR/LF so only the usable characters remain.
+        let i = input.trim().to_string();
+
+        // Custom commands are the same for every protocol.
+        if let Some(custom) = CustomReport::parse(&i) {
+            return CommReport::Custom(custom);
+        }
+
+        // Convert to &str for matching the command.
+        match i {
+            // XBoard commands
+            cmd if cmd == "xboard" => CommReport::Xboard(XboardReport::Xboard),
+            cmd if cmd == "new" => CommReport::Xboard(XboardReport::New),
+            cmd if cmd == "force" => CommReport::Xboard(XboardReport::Force),
+            cmd if cmd == "go" => CommReport::Xboard(XboardReport::Go),
+            cmd if cmd == "playother" => CommReport::Xboard(XboardReport::PlayOther),
+            cmd if cmd == "undo" => CommReport::Xboard(XboardReport::Undo),
+            cmd if cmd == "remove" => CommReport::Xboard(XboardReport::Remove),
+            cmd if cmd == "post" => CommReport::Xboard(XboardReport::Post),
+            cmd if cmd == "nopost" => CommReport::Xboard(XboardReport::NoPost),
+            cmd if cmd == "?" => CommReport::Xboard(XboardReport::MoveNow),
+            cmd if cmd == "quit" || cmd == "exit" => CommReport::Xboard(XboardReport::Quit),
+            cmd if cmd.starts_with("result") => CommReport::Xboard(XboardReport::Result),
+            cmd if cmd.starts_with("protover") => Xboard::parse_protover(&cmd),
+            cmd if cmd.starts_with("usermove") => Xboard::parse_usermove(&cmd),
+            cmd if cmd.starts_with("setboard") => Xboard::parse_setboard(&cmd),
+            cmd if cmd.starts_with("level") => Xboard::parse_level(&cmd),
+            cmd if cmd.starts_with("ping") => Xboard::parse_ping(&cmd),
+            cmd if cmd.starts_with("memory") => Xboard::parse_memory(&cmd),
+            cmd if cmd.starts_with("st ") => Xboard::parse_st(&cmd),
+            cmd if cmd.starts_with("sd ") => Xboard::parse_sd(&cmd),
+            cmd if cmd.starts_with("time ") => Xboard::parse_time(&cmd, false),
+            cmd if cmd.starts_with("otim ") => Xboard::parse_time(&cmd, true),
+
+            // Commands the engine knows about, but doesn't act upon.
+            cmd if cmd.is_empty() => CommReport::Xboard(XboardReport::Ignore),
+            cmd if Xboard::is_ignored(&cmd) => CommReport::Xboard(XboardReport::Ignore),
+
+            // If the GUI didn't accept the "usermove" feature, moves
+            // arrive without a command in front of them.
+            cmd if parse::algebraic_move_to_number(&cmd).is_ok() => {
+                CommReport::Xboard(XboardReport::UserMove(cmd))
+            }
+
+            // Everything else is unknown.
+            cmd => CommReport::Xboard(XboardReport::Unknown(cmd)),
+        }
+    }
+
+    // These commands can be sent by the GUI, but the engine has no use
+    // for them. They should not be answered with an error.
+    fn is_ignored(cmd: &str) -> bool {
+        const IGNORED: [&str; 13] = [
+            "accepted", "rejected", "random", "hard", "easy", "computer", "name", "rating", "ics",
+            "draw", "variant", "white", "black",
+        ];
+        let first = cmd.split_whitespace().next().unwrap_or("");
+
+        IGNORED.contains(&first)
+    }
+
+    // Returns the second token of the command, if available.
+    fn argument(cmd: &str) -> String {
+        cmd.split_whitespace().nth(1).unwrap_or("").to_string()
+    }
+
+    fn parse_protover(cmd: &str) -> CommReport {
+        let version = Xboard::argument(cmd).parse::<u8>().unwrap_or(1);
+        CommReport::Xboard(XboardReport::ProtoVer(version))
+    }
+
+    fn parse_usermove(cmd: &str) -> CommReport {
+        CommReport::Xboard(XboardReport::UserMove(Xboard::argument(cmd)))
+    }
+
+    fn parse_setboard(cmd: &str) -> CommReport {
+        let fen = cmd.trim_start_matches("setboard").trim().to_string();
+        CommReport::Xboard(XboardReport::SetBoard(fen))
+    }
+
+    fn parse_ping(cmd: &str) -> CommReport {
+        let n = Xboard::argument(cmd).parse::<isize>().unwrap_or(0);
+        CommReport::Xboard(XboardReport::Ping(n))
+    }
+
+    fn parse_memory(cmd: &str) -> CommReport {
+        match Xboard::argument(cmd).parse::<usize>() {
+            Ok(mb) => CommReport::Xboard(XboardReport::Memory(mb)),
+            Err(_) => CommReport::Xboard(XboardReport::Unknown(cmd.to_string())),
+        }
+    }
+
+    // "st" gives the exact number of seconds to spend on each move.
+    fn parse_st(cmd: &str) -> CommReport {
+        let seconds = Xboard::argument(cmd).parse::<f64>().unwrap_or(0.0);
+        let msecs = (seconds * 1000.0).round() as u128;
+        CommReport::Xboard(XboardReport::St(msecs))
+    }
+
+    fn parse_sd(cmd: &str) -> CommReport {
+        let depth = Xboard::argument(cmd).parse::<i8>().unwrap_or(0);
+        CommReport::Xboard(XboardReport::Sd(depth))
+    }
+
+    // "time" and "otim" provide the clocks in centiseconds.
+    fn parse_time(cmd: &str, opponent: bool) -> CommReport {
+        let centiseconds = Xboard::argument(cmd).parse::<u128>().unwrap_or(0);
+        let msecs = centiseconds * 10;
+        let report = if opponent {
+            XboardReport::OTim(msecs)
+        } else {
+            XboardReport::Time(msecs)
+        };
+        CommReport::Xboard(report)
+    }
+
+    // "level MPS BASE INC": moves per session, base time in minutes (or
+    // minutes:seconds), and increment in seconds.
+    fn parse_level(cmd: &str) -> CommReport {
+        let parts: Vec<&str> = cmd.split_whitespace().collect();
+
+        if parts.len() != 4 {
+            return CommReport::Xboard(XboardReport::Unknown(cmd.to_string()));
+        }
+
+        let moves_per_session = parts[1].parse::<usize>().unwrap_or(0);
+        let base: Vec<u128> = parts[2]
+            .split(':')
+            .map(|p| p.parse::<u128>().unwrap_or(0))
+            .collect();
+        let base_time = match base.len() {
+            2 => (base[0] * 60 + base[1]) * 1000,
+            _ => base[0] * 60 * 1000,
+        };
+        let increment = (parts[3].parse::<f64>().unwrap_or(0.0) * 1000.0).round() as u128;
+
+        CommReport::Xboard(XboardReport::Level(moves_per_session, base_time, increment))
+    }
+}
+
+// Implements XBoard responses to send to the G(UI).
+impl Xboard {
+    fn features() {
+        let features = [
+            format!("myname=\"{} {}\"", About::ENGINE, About::VERSION),
+            String::from("ping=1"),
+            String::from("setboard=1"),
+            String::from("usermove=1"),
+            String::from("playother=1"),
+            String::from("memory=1"),
+            String::from("colors=0"),
+            String::from("sigint=0"),
+            String::from("sigterm=0"),
+            String::from("analyze=0"),
+            String::from("done=1"),
+        ];
+
+        println!("feature done=0");
+        for f in features.iter() {
+            println!("feature {f}");
+        }
+    }
+
+    fn search_summary(s: &SearchSummary, chess960: bool, console: bool) {
+        // XBoard has no way to show that a score is a bound.
+        if s.bound != ScoreBound::Exact {
+            return;
+        }
+
+        // If mate found, report this; otherwise report normal score.
+        let score = if (s.cp.abs() >= CHECKMATE_THRESHOLD) && (s.cp.abs() < CHECKMATE) {
+            // Number of plies and moves to mate.
+            let ply = (CHECKMATE - s.cp.abs()) as i32;
+            let moves = (ply + 1) / 2;
+
+            // If the engine is being mated itself, flip the score.
+            if s.cp < 0 {
+                -(MATE_SCORE + moves)
+            } else {
+                MATE_SCORE + moves
+            }
+        } else {
+            s.cp as i32
+        };
+
+        // XBoard wants the time in centiseconds.
+        let centiseconds = s.time / 10;
+
+        let pv = if console {
+            s.pv_as_san()
+        } else {
+            s.pv_as_string(chess960)
+        };
+
+        println!("{} {} {} {} {}", s.depth, score, centiseconds, s.nodes, pv);
+    }
+
+    fn info_string(msg: &str) {
+        println!("# {msg}");
+    }
+
+    fn best_move(m: &Move, chess960: bool) {
+        println!("move {}", m.as_string(chess960));
+    }
+
+    fn pong(n: isize) {
+        println!("pong {n}");
+    }
+
+    fn illegal_move(m: &str) {
+        println!("Illegal move: {m}");
+    }
+
+    fn error(error_type: &str, cmd: &str) {
+        println!("Error ({error_type}): {cmd}");
     }
 }
@@ -25,6 +25,9 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 // within specific modules, are defined in defs.rs in the directory for
 // that module.
 
+use crate::board::defs::FenError;
+use std::fmt;
+
 pub struct About;
 impl About {
     pub const ENGINE: &'static str = "Rustic Alpha";
@@ -75,15 +78,28 @@ pub const MAX_LEGAL_MOVES: u8 = 255;
 pub const MAX_PLY: i8 = 125;
 pub const MAX_MOVE_RULE: u8 = 100; // 50/75 move rule
 
-// Define errors
-pub type EngineRunResult = Result<(), u8>;
-pub const ENGINE_RUN_ERRORS: [&str; 8] = [
-    "FEN: Must have six parts",
-    "FEN: Pieces and squares incorrect",
-    "FEN: Color selection incorrect",
-    "FEN: Castling permissions incorrect",
-    "FEN: En-passant square incorrect",
-    "FEN: Half-move clock incorrect",
-    "FEN: Full-move number incorrect",
-    "XBoard not yet implemented.",
-];
+// Define errors. The engine fails to run if the position it should start
+// with can't be set up, or if the EPD test suite finds a problem.
+pub enum EngineError {
+    Fen(FenError),
+    #[cfg(feature = "extra")]
+    EpdTest(String),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::Fen(e) => write!(f, "{e}"),
+            #[cfg(feature = "extra")]
+            EngineError::EpdTest(msg) => write!(f, "EPD test: {msg}"),
+        }
+    }
+}
+
+impl From<FenError> for EngineError {
+    fn from(e: FenError) -> Self {
+        EngineError::Fen(e)
+    }
+}
+
+pub type EngineRunResult = Result<(), EngineError>;
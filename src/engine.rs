@@ -26,28 +26,33 @@ mod comm_reports;
 pub mod defs;
 mod main_loop;
 mod search_reports;
+mod tactics;
 mod transposition;
 mod utils;
 
 use crate::{
     board::Board,
-    comm::{uci::Uci, CommControl, CommType, IComm},
+    comm::{uci::Uci, xboard::Xboard, CommControl, CommType, IComm},
     defs::EngineRunResult,
     engine::defs::{
-        EngineOption, EngineOptionDefaults, EngineOptionName, ErrFatal, Information, Settings,
-        UiElement,
+        EngineOption, EngineOptionDefaults, EngineOptionName, ErrFatal, GameScores, Information,
+        Settings, UiElement, XboardState,
     },
-    misc::{cmdline::CmdLine, perft},
+    misc::{cmdline::CmdLine, perft, pgn, print},
     movegen::MoveGenerator,
     search::{defs::SearchControl, Search},
 };
 use crossbeam_channel::Receiver;
-use std::sync::{Arc, Mutex};
+use std::{
+    io::{self, IsTerminal},
+    sync::{Arc, Mutex, RwLock},
+};
 use transposition::{PerftData, SearchData, TT};
 
 #[cfg(feature = "extra")]
 use crate::{
     board::defs::Pieces,
+    defs::EngineError,
     extra::{testsuite, wizardry},
 };
 
@@ -60,12 +65,14 @@ pub struct Engine {
     cmdline: CmdLine,                       // Command line interpreter.
     comm: Box<dyn IComm>,                   // Communications (active).
     board: Arc<Mutex<Board>>,               // This is the main engine board.
-    tt_perft: Arc<Mutex<TT<PerftData>>>,    // TT for running perft.
-    tt_search: Arc<Mutex<TT<SearchData>>>,  // TT for search information.
+    tt_perft: Arc<RwLock<TT<PerftData>>>,   // TT for running perft.
+    tt_search: Arc<RwLock<TT<SearchData>>>, // TT for search information.
     mg: Arc<MoveGenerator>,                 // Move Generator.
     info_rx: Option<Receiver<Information>>, // Receiver for incoming information.
     search: Search,                         // Search object (active).
-    tmp_no_xboard: bool,                    // Temporary variable to disable xBoard
+    searching: bool,                        // The search thread is busy.
+    xboard: XboardState,                    // Game state when using XBoard.
+    scores: GameScores,                     // Scores of the moves played.
 }
 
 impl Engine {
@@ -76,20 +83,19 @@ impl Engine {
 
         // Create the command-line object.
         let cmdline = CmdLine::new();
-        let mut is_xboard = false;
 
         // Create the communication interface
         let comm: Box<dyn IComm> = match &cmdline.comm()[..] {
-            CommType::XBOARD => {
-                is_xboard = true;
-                Box::new(Uci::new())
-            }
+            CommType::XBOARD => Box::new(Xboard::new()),
             CommType::UCI => Box::new(Uci::new()),
             _ => panic!("{}", ErrFatal::CREATE_COMM),
         };
 
         // Get engine settings from the command-line.
-        let threads = cmdline.threads();
+        let threads = cmdline.threads().clamp(
+            EngineOptionDefaults::THREADS_MIN,
+            EngineOptionDefaults::THREADS_MAX,
+        );
         let quiet = cmdline.has_quiet();
         let tt_size = cmdline.hash();
         let tt_max = if is_64_bit {
@@ -107,6 +113,41 @@ impl Engine {
                 Some(EngineOptionDefaults::HASH_MIN.to_string()),
                 Some(tt_max.to_string()),
             ),
+            EngineOption::new(
+                EngineOptionName::THREADS,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::THREADS_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::THREADS_MIN.to_string()),
+                Some(EngineOptionDefaults::THREADS_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionName::MULTI_PV,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::MULTI_PV_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::MULTI_PV_MIN.to_string()),
+                Some(EngineOptionDefaults::MULTI_PV_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionName::PONDER,
+                UiElement::Check,
+                Some(EngineOptionDefaults::PONDER_DEFAULT.to_string()),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionName::CHESS960,
+                UiElement::Check,
+                Some(EngineOptionDefaults::CHESS960_DEFAULT.to_string()),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionName::NULL_MOVE_VERIFICATION,
+                UiElement::Check,
+                Some(EngineOptionDefaults::NULL_MOVE_VERIFICATION_DEFAULT.to_string()),
+                None,
+                None,
+            ),
             EngineOption::new(
                 EngineOptionName::CLEAR_HASH,
                 UiElement::Button,
@@ -117,14 +158,14 @@ impl Engine {
         ];
 
         // Initialize correct TT.
-        let tt_perft: Arc<Mutex<TT<PerftData>>>;
-        let tt_search: Arc<Mutex<TT<SearchData>>>;
+        let tt_perft: Arc<RwLock<TT<PerftData>>>;
+        let tt_search: Arc<RwLock<TT<SearchData>>>;
         if cmdline.perft() > 0 {
-            tt_perft = Arc::new(Mutex::new(TT::<PerftData>::new(tt_size)));
-            tt_search = Arc::new(Mutex::new(TT::<SearchData>::new(0)));
+            tt_perft = Arc::new(RwLock::new(TT::<PerftData>::new(tt_size)));
+            tt_search = Arc::new(RwLock::new(TT::<SearchData>::new(0)));
         } else {
-            tt_perft = Arc::new(Mutex::new(TT::<PerftData>::new(0)));
-            tt_search = Arc::new(Mutex::new(TT::<SearchData>::new(tt_size)));
+            tt_perft = Arc::new(RwLock::new(TT::<PerftData>::new(0)));
+            tt_search = Arc::new(RwLock::new(TT::<SearchData>::new(tt_size)));
         };
 
         // Create the engine itself.
@@ -134,6 +175,11 @@ impl Engine {
                 threads,
                 quiet,
                 tt_size,
+                multi_pv: EngineOptionDefaults::MULTI_PV_DEFAULT,
+                ponder: EngineOptionDefaults::PONDER_DEFAULT,
+                verification: EngineOptionDefaults::NULL_MOVE_VERIFICATION_DEFAULT,
+                console: io::stdin().is_terminal(),
+                pending_tt_size: None,
             },
             options: Arc::new(options),
             cmdline,
@@ -144,18 +190,14 @@ impl Engine {
             tt_search,
             info_rx: None,
             search: Search::new(),
-            tmp_no_xboard: is_xboard,
+            searching: false,
+            xboard: XboardState::new(),
+            scores: GameScores::new(),
         }
     }
 
     // Run the engine.
     pub fn run(&mut self) -> EngineRunResult {
-        // This is temporary. Quit the engine immediately if anyone tries
-        // to start it in XBoard mode, as this is not implemented yet.
-        if self.tmp_no_xboard {
-            return Err(7);
-        }
-
         self.print_ascii_logo();
         self.print_about(&self.settings);
         println!();
@@ -169,15 +211,40 @@ impl Engine {
         // Run perft if requested.
         if self.cmdline.perft() > 0 {
             action_requested = true;
-            perft::run(
+            let run = if self.cmdline.has_divide() {
+                perft::run_divide
+            } else {
+                perft::run
+            };
+            run(
                 self.board.clone(),
                 self.cmdline.perft(),
                 Arc::clone(&self.mg),
                 Arc::clone(&self.tt_perft),
                 self.settings.tt_size > 0,
+                self.settings.threads,
             );
         }
 
+        // Print the positions of the games in a PGN-file if requested.
+        if let Some(file) = self.cmdline.pgn() {
+            action_requested = true;
+            pgn::print_positions(&file, &self.mg);
+        }
+
+        // Run the bench if requested.
+        if let Some(depth) = self.cmdline.bench() {
+            action_requested = true;
+            let result = self.bench(depth);
+            print::bench(&result);
+        }
+
+        // Run a test suite (such as WAC) if requested.
+        if let Some(file) = self.cmdline.suite() {
+            action_requested = true;
+            self.run_suite(&file);
+        }
+
         // === Only available with "extra" features enabled. ===
         #[cfg(feature = "extra")]
         // Generate magic numbers if requested.
@@ -188,7 +255,7 @@ impl Engine {
         };
 
         #[cfg(feature = "extra")]
-        // Run large EPD test suite if requested. Because the -p (perft)
+        // Run the EPD perft suite if requested. Because the -p (perft)
         // option is not used in this scenario, the engine initializes the
         // search TT instead of the one for perft. The -e option is
         // not available in a non-extra compilation, so it cannot be
@@ -197,11 +264,18 @@ impl Engine {
         if self.cmdline.has_test() {
             action_requested = true;
             self.tt_perft
-                .lock()
+                .write()
                 .expect(ErrFatal::LOCK)
                 .resize(self.settings.tt_size);
-            self.tt_search.lock().expect(ErrFatal::LOCK).resize(0);
-            testsuite::run(Arc::clone(&self.tt_perft), self.settings.tt_size > 0);
+            self.tt_search.write().expect(ErrFatal::LOCK).resize(0);
+            testsuite::run(
+                self.cmdline.test_file().as_deref(),
+                self.cmdline.depth(),
+                Arc::clone(&self.tt_perft),
+                self.settings.tt_size > 0,
+                self.settings.threads,
+            )
+            .map_err(EngineError::EpdTest)?;
         }
         // =====================================================
 
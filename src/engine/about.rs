@@ -50,11 +50,6 @@ impl Engine {
         } else {
             format!("{} MB", s.tt_size)
         };
-        let threads = if s.threads == 1 {
-            String::from("1")
-        } else {
-            format!("{} (unused, always 1)", s.threads)
-        };
 
         println!("{:<10} {} {}", "Engine:", About::ENGINE, About::VERSION);
         println!("{:<10} {}", "Author:", About::AUTHOR);
@@ -62,7 +57,7 @@ impl Engine {
         println!("{:<10} {}", "Website:", About::WEBSITE);
         println!("{:<10} {bits}-bit", "Type:");
         println!("{:<10} {hash}", "Hash:");
-        println!("{:<10} {threads}", "Threads:");
+        println!("{:<10} {}", "Threads:", s.threads);
 
         #[cfg(debug_assertions)]
         println!("{NOTICE_DEBUG_MODE}");
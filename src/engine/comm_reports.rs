@@ -22,15 +22,15 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 use super::{
-    defs::{ErrFatal, ErrNormal},
+    defs::{ErrFatal, ErrNormal, XboardState},
     Engine,
 };
 use crate::{
-    comm::{uci::UciReport, CommControl, CommReport},
-    defs::FEN_START_POSITION,
-    engine::defs::EngineOptionName,
+    comm::{uci::UciReport, xboard::XboardReport, CommControl, CommReport, CustomReport},
+    defs::{Sides, FEN_START_POSITION},
+    engine::defs::{EngineOptionDefaults, EngineOptionName},
     evaluation::evaluate_position,
-    search::defs::{SearchControl, SearchMode, SearchParams, OVERHEAD},
+    search::defs::{GameTime, SearchControl, SearchParams, OVERHEAD},
 };
 
 // This block implements handling of incoming information, which will be in
@@ -40,15 +40,53 @@ impl Engine {
         // Split out the comm reports according to their source.
         match comm_report {
             CommReport::Uci(u) => self.comm_reports_uci(u),
+            CommReport::Xboard(x) => self.comm_reports_xboard(x),
+            CommReport::Custom(c) => self.comm_reports_custom(c),
+        }
+    }
+
+    // Handles custom commands, which are the same for every protocol.
+    fn comm_reports_custom(&mut self, c: &CustomReport) {
+        match c {
+            CustomReport::Board => self.comm.send(CommControl::PrintBoard),
+            CustomReport::History => {
+                let history = self.history();
+                self.comm.send(CommControl::PrintHistory(history));
+            }
+            CustomReport::Fen => {
+                let fen = self.board.lock().expect(ErrFatal::LOCK).fen_write();
+                self.comm.send(CommControl::PrintFen(fen));
+            }
+            CustomReport::Pgn => {
+                let pgn = self.pgn();
+                self.comm.send(CommControl::PrintPgn(pgn));
+            }
+            // Perft and bench run on the engine thread. While a search is
+            // running, they would hold up "stop", and the bench would
+            // clear the TT the search is using.
+            CustomReport::Perft(_) | CustomReport::Bench(_) if self.searching => {
+                let msg = String::from(ErrNormal::SEARCHING);
+                self.comm.send(CommControl::InfoString(msg));
+            }
+            CustomReport::Perft(depth) => {
+                let (result, elapsed) = self.divide(*depth);
+                self.comm.send(CommControl::PrintDivide(result, elapsed));
+            }
+            CustomReport::Bench(depth) => {
+                let result = self.bench(*depth);
+                self.comm.send(CommControl::PrintBench(result));
+            }
+            CustomReport::Eval => {
+                let e = evaluate_position(&self.board.lock().expect(ErrFatal::LOCK));
+                let msg = format!("Evaluation: {e} centipawns");
+                self.comm.send(CommControl::InfoString(msg));
+            }
+            CustomReport::Help => self.comm.send(CommControl::PrintHelp),
         }
     }
 
     // Handles "Uci" Comm reports sent by the UCI-module.
     fn comm_reports_uci(&mut self, u: &UciReport) {
-        // Setup default variables.
-        let mut sp = SearchParams::new();
-        sp.quiet = self.settings.quiet;
-
         match u {
             UciReport::Uci => self.comm.send(CommControl::Identify),
 
@@ -56,9 +94,10 @@ impl Engine {
                 self.board
                     .lock()
                     .expect(ErrFatal::LOCK)
-                    .fen_read(Some(FEN_START_POSITION))
+                    .fen_read(Some(FEN_START_POSITION), &self.mg)
                     .expect(ErrFatal::NEW_GAME);
-                self.tt_search.lock().expect(ErrFatal::LOCK).clear();
+                self.tt_search.read().expect(ErrFatal::LOCK).clear();
+                self.scores.clear();
             }
 
             UciReport::IsReady => self.comm.send(CommControl::Ready),
@@ -67,7 +106,7 @@ impl Engine {
                 match option {
                     EngineOptionName::Hash(value) => {
                         if let Ok(v) = value.parse::<usize>() {
-                            self.tt_search.lock().expect(ErrFatal::LOCK).resize(v);
+                            self.resize_tt(v);
                         } else {
                             let msg = String::from(ErrNormal::NOT_INT);
                             self.comm.send(CommControl::InfoString(msg));
@@ -75,7 +114,44 @@ impl Engine {
                     }
 
                     EngineOptionName::ClearHash => {
-                        self.tt_search.lock().expect(ErrFatal::LOCK).clear()
+                        self.tt_search.read().expect(ErrFatal::LOCK).clear()
+                    }
+
+                    EngineOptionName::Threads(value) => {
+                        if let Ok(v) = value.parse::<usize>() {
+                            self.settings.threads = v.clamp(
+                                EngineOptionDefaults::THREADS_MIN,
+                                EngineOptionDefaults::THREADS_MAX,
+                            );
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_INT);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::MultiPv(value) => {
+                        if let Ok(v) = value.parse::<usize>() {
+                            self.settings.multi_pv = v.clamp(
+                                EngineOptionDefaults::MULTI_PV_MIN,
+                                EngineOptionDefaults::MULTI_PV_MAX,
+                            );
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_INT);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::Ponder(value) => {
+                        self.settings.ponder = value == "true";
+                    }
+
+                    EngineOptionName::Chess960(value) => {
+                        let mut board = self.board.lock().expect(ErrFatal::LOCK);
+                        board.chess960 = value == "true";
+                    }
+
+                    EngineOptionName::NullMoveVerification(value) => {
+                        self.settings.verification = value == "true";
                     }
 
                     EngineOptionName::Nothing => (),
@@ -83,7 +159,11 @@ impl Engine {
             }
 
             UciReport::Position(fen, moves) => {
-                let fen_result = self.board.lock().expect(ErrFatal::LOCK).fen_read(Some(fen));
+                let fen_result = self
+                    .board
+                    .lock()
+                    .expect(ErrFatal::LOCK)
+                    .fen_read(Some(fen), &self.mg);
 
                 if fen_result.is_ok() {
                     for m in moves.iter() {
@@ -96,54 +176,236 @@ impl Engine {
                     }
                 }
 
-                if fen_result.is_err() {
-                    let msg = ErrNormal::FEN_FAILED.to_string();
+                if let Err(e) = fen_result {
+                    let msg = format!("{e}. {}", ErrNormal::FEN_FAILED);
                     self.comm.send(CommControl::InfoString(msg));
                 }
             }
 
-            UciReport::GoInfinite => {
-                sp.search_mode = SearchMode::Infinite;
+            UciReport::Go(params, search_moves) => {
+                let mut sp = params.clone();
+                sp.quiet = self.settings.quiet;
+                sp.threads = self.settings.threads;
+                sp.multi_pv = self.settings.multi_pv;
+                sp.verification = self.settings.verification;
+                sp.console = self.settings.console;
+                sp.search_moves = self.search_moves(search_moves);
+
+                // Keep some time in reserve for communication with the GUI.
+                sp.move_time = sp.move_time.map(|t| t.saturating_sub(OVERHEAD as u128));
+
+                self.searching = true;
                 self.search.send(SearchControl::Start(sp));
             }
 
-            UciReport::GoDepth(depth) => {
-                sp.depth = *depth;
-                sp.search_mode = SearchMode::Depth;
-                self.search.send(SearchControl::Start(sp));
+            UciReport::PonderHit => self.search.send(SearchControl::PonderHit),
+            UciReport::Stop => self.search.send(SearchControl::Stop),
+            UciReport::Quit => self.quit(),
+            UciReport::Unknown => (),
+        }
+    }
+}
+
+// This block implements handling of XBoard Comm reports.
+impl Engine {
+    // Handles "XBoard" Comm reports sent by the XBoard-module.
+    fn comm_reports_xboard(&mut self, x: &XboardReport) {
+        match x {
+            XboardReport::Xboard => (),
+            XboardReport::ProtoVer(_) => self.comm.send(CommControl::Identify),
+
+            XboardReport::New => {
+                self.xboard_stop(true);
+                self.board
+                    .lock()
+                    .expect(ErrFatal::LOCK)
+                    .fen_read(Some(FEN_START_POSITION), &self.mg)
+                    .expect(ErrFatal::NEW_GAME);
+                self.tt_search.read().expect(ErrFatal::LOCK).clear();
+                self.scores.clear();
+
+                // Keep the post setting and the time control; reset the rest.
+                // A search that is still winding down must be discarded.
+                let x = &self.xboard;
+                self.xboard = XboardState {
+                    post: x.post,
+                    moves_per_session: x.moves_per_session,
+                    base_time: x.base_time,
+                    increment: x.increment,
+                    time: x.base_time,
+                    otim: x.base_time,
+                    discard: x.discard,
+                    ..XboardState::new()
+                };
             }
 
-            UciReport::GoMoveTime(msecs) => {
-                sp.move_time = *msecs - (OVERHEAD as u128);
-                sp.search_mode = SearchMode::MoveTime;
-                self.search.send(SearchControl::Start(sp));
+            XboardReport::Force => {
+                self.xboard_stop(true);
+                self.xboard.force = true;
             }
 
-            UciReport::GoNodes(nodes) => {
-                sp.nodes = *nodes;
-                sp.search_mode = SearchMode::Nodes;
-                self.search.send(SearchControl::Start(sp));
+            XboardReport::Go => {
+                self.xboard.force = false;
+                self.xboard.engine_side = self.board.lock().expect(ErrFatal::LOCK).us();
+                self.xboard_go();
             }
 
-            UciReport::GoGameTime(gt) => {
-                sp.game_time = *gt;
-                sp.search_mode = SearchMode::GameTime;
-                self.search.send(SearchControl::Start(sp));
+            XboardReport::PlayOther => {
+                self.xboard.force = false;
+                self.xboard.engine_side = self.board.lock().expect(ErrFatal::LOCK).opponent();
             }
 
-            UciReport::Stop => self.search.send(SearchControl::Stop),
-            UciReport::Quit => self.quit(),
+            XboardReport::UserMove(m) => {
+                if self.execute_move(m.clone()) {
+                    let us = self.board.lock().expect(ErrFatal::LOCK).us();
+                    if !self.xboard.force && us == self.xboard.engine_side {
+                        self.xboard_go();
+                    }
+                } else {
+                    self.comm.send(CommControl::IllegalMove(m.clone()));
+                }
+            }
 
-            // Custom commands
-            UciReport::Board => self.comm.send(CommControl::PrintBoard),
-            UciReport::History => self.comm.send(CommControl::PrintHistory),
-            UciReport::Eval => {
-                let e = evaluate_position(&self.board.lock().expect(ErrFatal::LOCK));
-                let msg = format!("Evaluation: {e} centipawns");
-                self.comm.send(CommControl::InfoString(msg));
+            XboardReport::SetBoard(fen) => {
+                self.xboard_stop(true);
+                let fen_result = self
+                    .board
+                    .lock()
+                    .expect(ErrFatal::LOCK)
+                    .fen_read(Some(fen), &self.mg);
+
+                if let Err(e) = fen_result {
+                    let msg = format!("{e}. {}", ErrNormal::FEN_FAILED);
+                    self.comm.send(CommControl::Error(msg, fen.clone()));
+                }
             }
-            UciReport::Help => self.comm.send(CommControl::PrintHelp),
-            UciReport::Unknown => (),
+
+            XboardReport::Level(mps, base, inc) => {
+                self.xboard.moves_per_session = *mps;
+                self.xboard.base_time = *base;
+                self.xboard.increment = *inc;
+                self.xboard.time = *base;
+                self.xboard.otim = *base;
+                self.xboard.move_time = 0;
+            }
+
+            XboardReport::St(msecs) => self.xboard.move_time = *msecs,
+            XboardReport::Sd(depth) => self.xboard.depth = *depth,
+            XboardReport::Time(msecs) => self.xboard.time = *msecs,
+            XboardReport::OTim(msecs) => self.xboard.otim = *msecs,
+
+            XboardReport::Undo => self.xboard_undo(1),
+            XboardReport::Remove => self.xboard_undo(2),
+
+            XboardReport::Ping(n) => self.comm.send(CommControl::Pong(*n)),
+            XboardReport::Post => self.xboard.post = true,
+            XboardReport::NoPost => self.xboard.post = false,
+            XboardReport::MoveNow => self.xboard_stop(false),
+
+            XboardReport::Memory(mb) => self.resize_tt(*mb),
+
+            XboardReport::Result => {
+                self.xboard_stop(true);
+                self.xboard.force = true;
+            }
+
+            XboardReport::Quit => self.quit(),
+
+            XboardReport::Ignore => (),
+            XboardReport::Unknown(cmd) => {
+                let msg = String::from(ErrNormal::UNKNOWN_COMMAND);
+                self.comm.send(CommControl::Error(msg, cmd.clone()));
+            }
+        }
+    }
+
+    // Start a search for the engine's side. If the search thread is still
+    // busy (for example, because it is winding down a search that was
+    // stopped), the new search is started as soon as it has finished.
+    pub fn xboard_go(&mut self) {
+        if self.searching {
+            self.xboard.pending = true;
+            return;
+        }
+
+        let mut sp = SearchParams::new();
+        sp.quiet = self.settings.quiet;
+        sp.threads = self.settings.threads;
+        sp.multi_pv = self.settings.multi_pv;
+        sp.verification = self.settings.verification;
+        sp.console = self.settings.console;
+
+        // A fixed depth limits the search in addition to the clock. A
+        // fixed time per move replaces the clock.
+        if self.xboard.depth > 0 {
+            sp.depth = Some(self.xboard.depth);
+        }
+
+        if self.xboard.move_time > 0 {
+            let overhead = OVERHEAD as u128;
+            sp.move_time = Some(self.xboard.move_time.saturating_sub(overhead));
+        } else {
+            let white = self.xboard.engine_side == Sides::WHITE;
+            let (wtime, btime) = if white {
+                (self.xboard.time, self.xboard.otim)
+            } else {
+                (self.xboard.otim, self.xboard.time)
+            };
+            let inc = self.xboard.increment;
+            let mtg = self.xboard_moves_to_go();
+
+            sp.game_time = Some(GameTime::new(wtime, btime, inc, inc, mtg));
+        }
+
+        self.searching = true;
+        self.xboard.discard = false;
+        self.search.send(SearchControl::Start(sp));
+    }
+
+    // Stop the running search (if any). If the result is to be discarded,
+    // the engine will not play the move the search comes up with.
+    pub fn xboard_stop(&mut self, discard: bool) {
+        self.xboard.pending = false;
+
+        if self.searching {
+            self.xboard.discard = self.xboard.discard || discard;
+            self.search.send(SearchControl::Stop);
+        }
+    }
+
+    // Take back one or more moves, if there are enough in the history.
+    fn xboard_undo(&mut self, count: usize) {
+        self.xboard_stop(true);
+
+        let mut mtx_board = self.board.lock().expect(ErrFatal::LOCK);
+        if mtx_board.history.len() >= count {
+            for _ in 0..count {
+                mtx_board.unmake();
+            }
+        } else {
+            let msg = String::from(ErrNormal::NO_UNDO);
+            let cmd = if count == 1 { "undo" } else { "remove" };
+            self.comm.send(CommControl::Error(msg, cmd.to_string()));
+        }
+        std::mem::drop(mtx_board);
+    }
+
+    // Moves until the next time control. A session size of 0 means the
+    // entire game must be played within the time, so this is unknown.
+    fn xboard_moves_to_go(&self) -> Option<usize> {
+        let mps = self.xboard.moves_per_session;
+
+        if mps > 0 {
+            let fmn = self
+                .board
+                .lock()
+                .expect(ErrFatal::LOCK)
+                .game_state
+                .fullmove_number as usize;
+            let played = (fmn.max(1) - 1) % mps;
+            Some(mps - played)
+        } else {
+            None
         }
     }
 }
@@ -22,7 +22,13 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 pub use crate::engine::transposition::{HashFlag, IHashData, PerftData, SearchData, TT};
-use crate::{comm::CommReport, search::defs::SearchReport};
+use crate::{
+    comm::CommReport,
+    defs::{Side, Sides},
+    movegen::defs::ShortMove,
+    search::defs::SearchReport,
+};
+use std::collections::HashMap;
 
 // This struct holds messages that are reported on fatal engine errors.
 // These should never happen; if they do the engine is in an unknown state,
@@ -37,6 +43,7 @@ impl ErrFatal {
     pub const THREAD: &'static str = "Thread has failed.";
     pub const CHANNEL: &'static str = "Broken channel.";
     pub const NO_INFO_RX: &'static str = "No incoming Info channel.";
+    pub const BENCH: &'static str = "Setting up bench position failed.";
 }
 
 pub struct ErrNormal;
@@ -44,6 +51,9 @@ impl ErrNormal {
     pub const NOT_LEGAL: &'static str = "This is not a legal move in this position.";
     pub const NOT_INT: &'static str = "The value given was not an integer.";
     pub const FEN_FAILED: &'static str = "Setting up FEN failed. Board not changed.";
+    pub const UNKNOWN_COMMAND: &'static str = "unknown command";
+    pub const NO_UNDO: &'static str = "no moves to undo";
+    pub const SEARCHING: &'static str = "Not possible while the engine is searching.";
 }
 
 // This struct holds the engine's settings.
@@ -51,6 +61,72 @@ pub struct Settings {
     pub threads: usize,
     pub quiet: bool,
     pub tt_size: usize,
+    pub multi_pv: usize,
+    pub ponder: bool,
+    pub verification: bool,
+    pub console: bool,
+    pub pending_tt_size: Option<usize>,
+}
+
+// XBoard is a stateful protocol: the GUI sets up the clocks and the side
+// the engine plays once, and then only sends moves. The engine keeps track
+// of this state in the struct below.
+pub struct XboardState {
+    pub force: bool,              // Engine does not move by itself.
+    pub engine_side: Side,        // Side the engine is playing.
+    pub post: bool,               // Send thinking output to the GUI.
+    pub moves_per_session: usize, // Moves per time control (0 = whole game)
+    pub base_time: u128,          // Time per session in milliseconds
+    pub increment: u128,          // Increment per move in milliseconds
+    pub move_time: u128,          // Exact time per move ("st") in milliseconds
+    pub depth: i8,                // Maximum search depth ("sd")
+    pub time: u128,               // Engine's clock in milliseconds
+    pub otim: u128,               // Opponent's clock in milliseconds
+    pub discard: bool,            // Discard the result of the running search.
+    pub pending: bool,            // Start a new search when the current one ends.
+}
+
+impl XboardState {
+    // The defaults are the same as XBoard's own: 40 moves in 5 minutes.
+    pub fn new() -> Self {
+        Self {
+            force: false,
+            engine_side: Sides::BLACK,
+            post: false,
+            moves_per_session: 40,
+            base_time: 300_000,
+            increment: 0,
+            move_time: 0,
+            depth: 0,
+            time: 300_000,
+            otim: 300_000,
+            discard: false,
+            pending: false,
+        }
+    }
+}
+
+// The engine remembers the score of each move it played, so it can be
+// written into the PGN of the game. The score of the last finished
+// iteration is kept until the search ends; then it is stored with the
+// position (Zobrist key) and the move that was played.
+pub struct GameScores {
+    pub last: Option<String>,
+    pub played: HashMap<u64, (ShortMove, String)>,
+}
+
+impl GameScores {
+    pub fn new() -> Self {
+        Self {
+            last: None,
+            played: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.last = None;
+        self.played.clear();
+    }
 }
 
 // This enum provides informatin to the engine, with regard to incoming
@@ -64,6 +140,7 @@ pub enum Information {
 pub enum UiElement {
     Spin,
     Button,
+    Check,
 }
 
 pub struct EngineOption {
@@ -96,11 +173,21 @@ impl EngineOption {
 pub enum EngineOptionName {
     Hash(String),
     ClearHash,
+    Threads(String),
+    MultiPv(String),
+    Ponder(String),
+    Chess960(String),
+    NullMoveVerification(String),
     Nothing,
 }
 impl EngineOptionName {
     pub const HASH: &'static str = "Hash";
     pub const CLEAR_HASH: &'static str = "Clear Hash";
+    pub const THREADS: &'static str = "Threads";
+    pub const MULTI_PV: &'static str = "MultiPV";
+    pub const PONDER: &'static str = "Ponder";
+    pub const CHESS960: &'static str = "UCI_Chess960";
+    pub const NULL_MOVE_VERIFICATION: &'static str = "NullMoveVerification";
 }
 
 pub struct EngineOptionDefaults;
@@ -109,4 +196,13 @@ impl EngineOptionDefaults {
     pub const HASH_MIN: usize = 0;
     pub const HASH_MAX_64_BIT: usize = 65536;
     pub const HASH_MAX_32_BIT: usize = 2048;
+    pub const THREADS_DEFAULT: usize = 1;
+    pub const THREADS_MIN: usize = 1;
+    pub const THREADS_MAX: usize = 256;
+    pub const MULTI_PV_DEFAULT: usize = 1;
+    pub const MULTI_PV_MIN: usize = 1;
+    pub const MULTI_PV_MAX: usize = 64;
+    pub const PONDER_DEFAULT: bool = false;
+    pub const CHESS960_DEFAULT: bool = false;
+    pub const NULL_MOVE_VERIFICATION_DEFAULT: bool = false;
 }
@@ -21,14 +21,36 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-use super::Engine;
-use crate::{comm::CommControl, search::defs::SearchReport};
+use super::{defs::ErrFatal, Engine};
+use crate::{
+    comm::{CommControl, CommType},
+    misc::pgn,
+    movegen::defs::Move,
+    search::defs::{ScoreBound, SearchReport},
+};
 
 impl Engine {
     pub fn search_reports(&mut self, search_report: &SearchReport) {
+        let is_xboard = self.comm.get_protocol_name() == CommType::XBOARD;
+
         match search_report {
-            SearchReport::Finished(m) => {
-                self.comm.send(CommControl::BestMove(*m));
+            SearchReport::Finished(m, p) => {
+                self.searching = false;
+
+                // The search has released the TT, so it can be resized.
+                if let Some(megabytes) = self.settings.pending_tt_size.take() {
+                    self.resize_tt(megabytes);
+                }
+
+                if is_xboard {
+                    self.xboard_finished(*m);
+                } else {
+                    self.record_score(*m);
+
+                    // Only suggest a move to ponder on if pondering is on.
+                    let ponder_move = if self.settings.ponder { *p } else { None };
+                    self.comm.send(CommControl::BestMove(*m, ponder_move));
+                }
                 self.comm.send(CommControl::Update);
             }
 
@@ -37,12 +59,54 @@ impl Engine {
             }
 
             SearchReport::SearchSummary(summary) => {
-                self.comm.send(CommControl::SearchSummary(summary.clone()));
+                // Keep the score of the best line for the PGN.
+                if summary.multi_pv <= 1 && summary.bound == ScoreBound::Exact {
+                    let score = pgn::score_comment(summary.cp, summary.depth);
+                    self.scores.last = Some(score);
+                }
+
+                // XBoard only wants thinking output after "post".
+                if !is_xboard || self.xboard.post {
+                    self.comm.send(CommControl::SearchSummary(summary.clone()));
+                }
             }
 
             SearchReport::SearchStats(stats) => {
                 self.comm.send(CommControl::SearchStats(*stats));
             }
+
+            SearchReport::InfoString(msg) => {
+                self.comm.send(CommControl::InfoString(msg.clone()));
+            }
+        }
+    }
+}
+
+impl Engine {
+    // In XBoard mode the engine keeps track of the game itself. It plays
+    // the move on its own board before sending it, unless the search was
+    // stopped because the game changed underneath it.
+    fn xboard_finished(&mut self, m: Move) {
+        let discard = self.xboard.discard;
+
+        self.xboard.discard = false;
+
+        // A null move means there were no legal moves to play.
+        if !discard && m.get_move() != 0 {
+            self.record_score(m);
+            let mut mtx_board = self.board.lock().expect(ErrFatal::LOCK);
+            let is_legal = mtx_board.make(m, &self.mg);
+            std::mem::drop(mtx_board);
+
+            if is_legal {
+                self.comm.send(CommControl::BestMove(m, None));
+            }
+        }
+
+        // The GUI may already have asked for a new search.
+        if self.xboard.pending {
+            self.xboard.pending = false;
+            self.xboard_go();
         }
     }
 }
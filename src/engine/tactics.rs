@@ -0,0 +1,188 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2021, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// tactics.rs runs a test suite such as WAC, STS or ECM: an EPD-file with
+// positions and the best move ("bm") and/or the move to avoid ("am") in
+// each of them. The engine searches every position for a fixed time or
+// to a fixed depth. A position is solved if the engine comes up with one
+// of the best moves, and not with a move to avoid.
+
+use super::{
+    defs::{ErrFatal, Information},
+    Engine,
+};
+use crate::{
+    misc::{
+        epd::{self, Epd, Opcode},
+        pgn, san,
+    },
+    movegen::defs::Move,
+    search::defs::{SearchControl, SearchParams, SearchReport},
+};
+use std::sync::Arc;
+
+// Search time per position if neither time nor depth is given.
+const DEFAULT_MOVE_TIME: u128 = 1000;
+
+impl Engine {
+    pub fn run_suite(&mut self, file: &str) {
+        let epds = match epd::read_file(file) {
+            Ok(e) => e,
+            Err(e) => {
+                println!("Error: {e}");
+                return;
+            }
+        };
+
+        // The suite runs the search directly, without Comm.
+        let (info_tx, info_rx) = crossbeam_channel::unbounded::<Information>();
+        self.search.init(
+            info_tx,
+            Arc::clone(&self.board),
+            Arc::clone(&self.mg),
+            Arc::clone(&self.tt_search),
+            self.settings.tt_size > 0,
+        );
+
+        let mut solved = 0;
+        let mut skipped = 0;
+        for (nr, position) in epds.iter().enumerate() {
+            let label = position
+                .operand(Opcode::ID)
+                .or(position.operand(Opcode::COMMENT))
+                .unwrap_or("-")
+                .to_string();
+
+            // Skip positions that can't be set up.
+            let setup = self
+                .board
+                .lock()
+                .expect(ErrFatal::LOCK)
+                .fen_read(Some(&position.fen()), &self.mg);
+            if let Err(e) = setup {
+                println!("{:>4} {label:<16} skipped: {e}", nr + 1);
+                skipped += 1;
+                continue;
+            }
+
+            // Every position starts with an empty TT, so the results don't
+            // depend on the positions searched before.
+            self.tt_search.read().expect(ErrFatal::LOCK).clear();
+            self.search
+                .send(SearchControl::Start(self.suite_params(position)));
+
+            // Wait for the search to finish, keeping the last summary.
+            let mut result = String::from("");
+            let found = loop {
+                match info_rx.recv().expect(ErrFatal::CHANNEL) {
+                    Information::Search(SearchReport::Finished(m, _)) => break m,
+                    Information::Search(SearchReport::SearchSummary(s)) => {
+                        result = pgn::score_comment(s.cp, s.depth);
+                    }
+                    _ => (),
+                }
+            };
+
+            let is_solved = self.is_solved(position, found);
+            if is_solved {
+                solved += 1;
+            }
+
+            let board = self.board.lock().expect(ErrFatal::LOCK);
+            let found_san = san::move_to_san(&board, &self.mg, found);
+            std::mem::drop(board);
+
+            println!(
+                "{:>4} {label:<16} {:<8} found {found_san:<8} {result:<12} {}",
+                nr + 1,
+                if is_solved { "solved" } else { "unsolved" },
+                expected(position),
+            );
+        }
+
+        self.search.send(SearchControl::Quit);
+        self.search.wait_for_shutdown();
+
+        let tested = epds.len() - skipped;
+        let percentage = if tested == 0 {
+            0.0
+        } else {
+            (solved as f64 * 100.0) / tested as f64
+        };
+        println!();
+        println!("Solved {solved} of {tested} ({percentage:.1}%), skipped {skipped}");
+    }
+
+    // Search to the depth or for the time given on the command line. If
+    // neither is given, the position's "acd" (depth) is used if it has one.
+    fn suite_params(&self, position: &Epd) -> SearchParams {
+        let mut sp = SearchParams::new();
+        let epd_depth = position
+            .operand(Opcode::DEPTH)
+            .and_then(|d| d.parse::<i8>().ok());
+
+        match (self.cmdline.depth(), self.cmdline.move_time()) {
+            (Some(d), _) => sp.depth = Some(d),
+            (None, Some(t)) => sp.move_time = Some(t),
+            (None, None) => match epd_depth {
+                Some(d) => sp.depth = Some(d),
+                None => sp.move_time = Some(DEFAULT_MOVE_TIME),
+            },
+        }
+
+        sp.quiet = true;
+        sp.threads = self.settings.threads;
+        sp.verification = self.settings.verification;
+        sp
+    }
+
+    // Checks the move found against the best moves and the moves to avoid,
+    // which are written in SAN.
+    fn is_solved(&self, position: &Epd, found: Move) -> bool {
+        let board = self.board.lock().expect(ErrFatal::LOCK);
+        let is_found = |opcode: &str| -> bool {
+            position.operands(opcode).is_some_and(|moves| {
+                moves.iter().any(|m| {
+                    san::san_to_move(&board, &self.mg, m)
+                        .is_some_and(|x| x.to_short_move() == found.to_short_move())
+                })
+            })
+        };
+
+        let is_best = position.operands(Opcode::BEST_MOVE).is_none() || is_found(Opcode::BEST_MOVE);
+        is_best && !is_found(Opcode::AVOID_MOVE)
+    }
+}
+
+// Lists the best moves and moves to avoid, as in "bm Qg6" or "am Nxe5".
+fn expected(position: &Epd) -> String {
+    [Opcode::BEST_MOVE, Opcode::AVOID_MOVE]
+        .iter()
+        .filter_map(|opcode| {
+            position
+                .operands(opcode)
+                .map(|moves| format!("{opcode} {}", moves.join(" ")))
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
@@ -22,19 +22,37 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 use crate::{board::defs::ZobristKey, movegen::defs::ShortMove, search::defs::CHECKMATE_THRESHOLD};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 
 const MEGABYTE: usize = 1024 * 1024;
 const ENTRIES_PER_BUCKET: usize = 4;
 const HIGH_FOUR_BYTES: u64 = 0xFF_FF_FF_FF_00_00_00_00;
-const LOW_FOUR_BYTES: u64 = 0x00_00_00_00_FF_FF_FF_FF;
 const SHIFT_TO_LOWER: u64 = 32;
+const HASH_FULL_SAMPLE: usize = 1000;
+const AGE_PENALTY: i32 = 4; // Depth an entry loses per generation of age.
 
 /* ===== Data ========================================================= */
 
+// Data stored in the TT must be able to pack itself into a single u64, so
+// it can be written and read atomically without locking the table. Data
+// that doesn't age (such as perft results) can use the default functions.
 pub trait IHashData {
-    fn new() -> Self;
     fn depth(&self) -> i8;
+    fn pack(&self) -> u64;
+    fn unpack(data: u64) -> Self;
+
+    fn age(&self) -> u8 {
+        0
+    }
+
+    fn set_age(&mut self, _age: u8) {}
 }
+
+// Layout of PerftData within a u64: depth in the lowest 8 bits, and the
+// number of leaf nodes in the remaining 56 bits.
+const PERFT_DEPTH_MASK: u64 = 0xFF;
+const PERFT_LEAF_NODES_SHIFT: u64 = 8;
+
 #[derive(Copy, Clone)]
 pub struct PerftData {
     depth: i8,
@@ -42,16 +60,20 @@ pub struct PerftData {
 }
 
 impl IHashData for PerftData {
-    fn new() -> Self {
-        Self {
-            depth: 0,
-            leaf_nodes: 0,
-        }
-    }
-
     fn depth(&self) -> i8 {
         self.depth
     }
+
+    fn pack(&self) -> u64 {
+        (self.leaf_nodes << PERFT_LEAF_NODES_SHIFT) | (self.depth as u8 as u64)
+    }
+
+    fn unpack(data: u64) -> Self {
+        Self {
+            depth: (data & PERFT_DEPTH_MASK) as u8 as i8,
+            leaf_nodes: data >> PERFT_LEAF_NODES_SHIFT,
+        }
+    }
 }
 
 impl PerftData {
@@ -76,26 +98,67 @@ pub enum HashFlag {
     Beta,
 }
 
+impl HashFlag {
+    fn from_bits(bits: u64) -> Self {
+        match bits {
+            1 => HashFlag::Exact,
+            2 => HashFlag::Alpha,
+            3 => HashFlag::Beta,
+            _ => HashFlag::Nothing,
+        }
+    }
+}
+
+// Layout of SearchData within a u64:
+// bits 0-23: best move, 24-39: value, 40-47: depth, 48-49: flag, 50-57: age.
+const SD_MOVE_MASK: u64 = 0xFF_FF_FF;
+const SD_VALUE_SHIFT: u64 = 24;
+const SD_VALUE_MASK: u64 = 0xFF_FF;
+const SD_DEPTH_SHIFT: u64 = 40;
+const SD_DEPTH_MASK: u64 = 0xFF;
+const SD_FLAG_SHIFT: u64 = 48;
+const SD_FLAG_MASK: u64 = 0x3;
+const SD_AGE_SHIFT: u64 = 50;
+const SD_AGE_MASK: u64 = 0xFF;
+
 #[derive(Copy, Clone)]
 pub struct SearchData {
     depth: i8,
     flag: HashFlag,
     value: i16,
     best_move: ShortMove,
+    age: u8,
 }
 
 impl IHashData for SearchData {
-    fn new() -> Self {
+    fn depth(&self) -> i8 {
+        self.depth
+    }
+
+    fn pack(&self) -> u64 {
+        (self.best_move.get_move() as u64 & SD_MOVE_MASK)
+            | ((self.value as u16 as u64) << SD_VALUE_SHIFT)
+            | ((self.depth as u8 as u64) << SD_DEPTH_SHIFT)
+            | ((self.flag as u64) << SD_FLAG_SHIFT)
+            | ((self.age as u64) << SD_AGE_SHIFT)
+    }
+
+    fn unpack(data: u64) -> Self {
         Self {
-            depth: 0,
-            flag: HashFlag::Nothing,
-            value: 0,
-            best_move: ShortMove::new(0),
+            depth: ((data >> SD_DEPTH_SHIFT) & SD_DEPTH_MASK) as u8 as i8,
+            flag: HashFlag::from_bits((data >> SD_FLAG_SHIFT) & SD_FLAG_MASK),
+            value: ((data >> SD_VALUE_SHIFT) & SD_VALUE_MASK) as u16 as i16,
+            best_move: ShortMove::new((data & SD_MOVE_MASK) as u32),
+            age: ((data >> SD_AGE_SHIFT) & SD_AGE_MASK) as u8,
         }
     }
 
-    fn depth(&self) -> i8 {
-        self.depth
+    fn age(&self) -> u8 {
+        self.age
+    }
+
+    fn set_age(&mut self, age: u8) {
+        self.age = age;
     }
 }
 
@@ -113,7 +176,7 @@ impl SearchData {
             v += ply as i16;
         }
 
-        if v < CHECKMATE_THRESHOLD {
+        if v < -CHECKMATE_THRESHOLD {
             v -= ply as i16;
         }
 
@@ -122,6 +185,7 @@ impl SearchData {
             flag,
             value: v,
             best_move,
+            age: 0,
         }
     }
 
@@ -143,23 +207,15 @@ impl SearchData {
                         v -= ply as i16;
                     }
 
-                    if v < CHECKMATE_THRESHOLD {
+                    if v < -CHECKMATE_THRESHOLD {
                         v += ply as i16;
                     }
 
                     // This is the value that will be returned.
                     value = Some(v);
                 }
-                HashFlag::Alpha => {
-                    if self.value <= alpha {
-                        value = Some(alpha);
-                    }
-                }
-                HashFlag::Beta => {
-                    if self.value >= beta {
-                        value = Some(beta);
-                    }
-                }
+                HashFlag::Alpha if self.value <= alpha => value = Some(alpha),
+                HashFlag::Beta if self.value >= beta => value = Some(beta),
                 _ => (),
             };
         }
@@ -169,63 +225,98 @@ impl SearchData {
 
 /* ===== Entry ======================================================== */
 
-#[derive(Copy, Clone)]
-struct Entry<D> {
-    verification: u32,
-    data: D,
+// An entry holds the packed data, and the Zobrist key XOR'ed with that
+// data. Both are atomics, so threads can read and write entries without
+// locking. If two threads write the same entry at the same time, the key
+// and data may come from different writes. This is detected on reading,
+// because then (key ^ data) will not result in the probed Zobrist key.
+struct Entry {
+    key: AtomicU64,
+    data: AtomicU64,
 }
 
-impl<D: IHashData> Entry<D> {
+impl Entry {
     pub fn new() -> Self {
         Self {
-            verification: 0,
-            data: D::new(),
+            key: AtomicU64::new(0),
+            data: AtomicU64::new(0),
         }
     }
+
+    // Returns the stored Zobrist key and the packed data.
+    pub fn load(&self) -> (ZobristKey, u64) {
+        let data = self.data.load(Ordering::Relaxed);
+        let key = self.key.load(Ordering::Relaxed) ^ data;
+
+        (key, data)
+    }
+
+    pub fn store(&self, zobrist_key: ZobristKey, data: u64) {
+        self.key.store(zobrist_key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        self.key.store(0, Ordering::Relaxed);
+        self.data.store(0, Ordering::Relaxed);
+    }
+
+    // An entry that was never written holds no data.
+    pub fn is_empty(&self) -> bool {
+        self.data.load(Ordering::Relaxed) == 0
+    }
 }
 
 /* ===== Bucket ======================================================= */
 
-#[derive(Clone)]
-struct Bucket<D> {
-    bucket: [Entry<D>; ENTRIES_PER_BUCKET],
+struct Bucket {
+    bucket: [Entry; ENTRIES_PER_BUCKET],
 }
 
-impl<D: IHashData + Copy> Bucket<D> {
+impl Bucket {
     pub fn new() -> Self {
         Self {
-            bucket: [Entry::new(); ENTRIES_PER_BUCKET],
+            bucket: std::array::from_fn(|_| Entry::new()),
         }
     }
 
-    // Store a position in the bucket. Replace the position with the stored
-    // lowest depth, as positions with higher depth are more valuable.
-    pub fn store(&mut self, verification: u32, data: D, used_entries: &mut usize) {
-        let mut idx_lowest_depth = 0;
-
-        // Find the index of the entry with the lowest depth.
-        for entry in 1..ENTRIES_PER_BUCKET {
-            if self.bucket[entry].data.depth() < data.depth() {
-                idx_lowest_depth = entry
+    // Store a position in the bucket. If the position is already in the
+    // bucket, it is overwritten. Otherwise, an empty entry is used, or the
+    // least valuable one is replaced. Positions searched to a higher depth
+    // are more valuable, but they lose value as they age: positions from
+    // previous searches are less likely to be needed again.
+    pub fn store<D: IHashData>(&self, zobrist_key: ZobristKey, data: D, generation: u8) {
+        let mut idx_replace = 0;
+        let mut lowest_value = i32::MAX;
+
+        for (i, entry) in self.bucket.iter().enumerate() {
+            let (key, packed) = entry.load();
+
+            if key == zobrist_key || entry.is_empty() {
+                idx_replace = i;
+                break;
             }
-        }
 
-        // If the verifiaction was 0, this entry in the bucket was never
-        // used before. Count the use of this entry.
-        if self.bucket[idx_lowest_depth].verification == 0 {
-            *used_entries += 1;
+            let stored = D::unpack(packed);
+            let age = generation.wrapping_sub(stored.age()) as i32;
+            let value = stored.depth() as i32 - AGE_PENALTY * age;
+
+            if value < lowest_value {
+                lowest_value = value;
+                idx_replace = i;
+            }
         }
 
-        // Store.
-        self.bucket[idx_lowest_depth] = Entry { verification, data }
+        self.bucket[idx_replace].store(zobrist_key, data.pack());
     }
 
-    // Find a position in the bucket, where both the stored verification and
-    // depth match the requested verification and depth.
-    pub fn find(&self, verification: u32) -> Option<&D> {
+    // Find a position in the bucket by its Zobrist key. A torn entry will
+    // not match, so it is never returned.
+    pub fn find<D: IHashData>(&self, zobrist_key: ZobristKey) -> Option<D> {
         for e in self.bucket.iter() {
-            if e.verification == verification {
-                return Some(&e.data);
+            let (key, data) = e.load();
+            if key == zobrist_key && data != 0 {
+                return Some(D::unpack(data));
             }
         }
         None
@@ -234,29 +325,32 @@ impl<D: IHashData + Copy> Bucket<D> {
 
 /* ===== TT =================================================== */
 
-// Transposition Table
+// Transposition Table. Inserting and probing only need a shared
+// reference, so the table can be used by multiple threads at once.
+// Resizing replaces the table and needs exclusive access.
 pub struct TT<D> {
-    tt: Vec<Bucket<D>>,
+    tt: Vec<Bucket>,
+    generation: AtomicU8,
     megabytes: usize,
-    used_entries: usize,
     total_buckets: usize,
     total_entries: usize,
+    _data: std::marker::PhantomData<D>,
 }
 
 // Public functions
-impl<D: IHashData + Copy + Clone> TT<D> {
+impl<D: IHashData> TT<D> {
     // Create a new TT of the requested size, able to hold the data
-    // of type D, where D has to implement IHashData, and must be clonable
-    // and copyable.
+    // of type D, where D has to implement IHashData.
     pub fn new(megabytes: usize) -> Self {
         let (total_buckets, total_entries) = Self::calculate_init_values(megabytes);
 
         Self {
-            tt: vec![Bucket::<D>::new(); total_buckets],
+            tt: Self::create_buckets(total_buckets),
+            generation: AtomicU8::new(0),
             megabytes,
-            used_entries: 0,
             total_buckets,
             total_entries,
+            _data: std::marker::PhantomData,
         }
     }
 
@@ -267,46 +361,73 @@ impl<D: IHashData + Copy + Clone> TT<D> {
     pub fn resize(&mut self, megabytes: usize) {
         let (total_buckets, total_entries) = TT::<D>::calculate_init_values(megabytes);
 
-        self.tt = vec![Bucket::<D>::new(); total_buckets];
+        // Drop the old table before allocating the new one.
+        self.tt = Vec::new();
+        self.tt = Self::create_buckets(total_buckets);
         self.megabytes = megabytes;
-        self.used_entries = 0;
         self.total_buckets = total_buckets;
         self.total_entries = total_entries;
     }
 
     // Insert a position at the calculated index, by storing it in the
-    // index's bucket.
-    pub fn insert(&mut self, zobrist_key: ZobristKey, data: D) {
+    // index's bucket. The data is stamped with the current generation.
+    pub fn insert(&self, zobrist_key: ZobristKey, mut data: D) {
         if self.megabytes > 0 {
             let index = self.calculate_index(zobrist_key);
-            let verification = self.calculate_verification(zobrist_key);
-            self.tt[index].store(verification, data, &mut self.used_entries);
+            let generation = self.generation();
+
+            data.set_age(generation);
+            self.tt[index].store(zobrist_key, data, generation);
         }
     }
 
-    // Probe the TT by both verification and depth. Both have to
-    // match for the position to be the correct one we're looking for.
-    pub fn probe(&self, zobrist_key: ZobristKey) -> Option<&D> {
+    // Start a new generation. This is done at the start of every search,
+    // so entries from earlier searches can be recognized as being old.
+    // (The generation wraps around after 256 searches.)
+    pub fn new_generation(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Probe the TT for the given position. The returned data is a copy,
+    // because other threads may overwrite the entry at any time.
+    pub fn probe(&self, zobrist_key: ZobristKey) -> Option<D> {
         if self.megabytes > 0 {
             let index = self.calculate_index(zobrist_key);
-            let verification = self.calculate_verification(zobrist_key);
-
-            self.tt[index].find(verification)
+            self.tt[index].find(zobrist_key)
         } else {
             None
         }
     }
 
-    // Clear TT by replacing it with a new one.
-    pub fn clear(&mut self) {
-        self.resize(self.megabytes);
+    // Clear the TT by emptying all of the entries.
+    pub fn clear(&self) {
+        self.generation.store(0, Ordering::Relaxed);
+
+        for bucket in self.tt.iter() {
+            for entry in bucket.bucket.iter() {
+                entry.clear();
+            }
+        }
     }
 
     // Provides TT usage in permille (1 per 1000, as oppposed to percent,
-    // which is 1 per 100.)
+    // which is 1 per 100.) Counting all used entries would need a shared
+    // counter, so estimate the usage from the first entries in the table.
+    // Only entries written during the current generation count as used;
+    // older entries are available for replacement.
     pub fn hash_full(&self) -> u16 {
         if self.megabytes > 0 {
-            ((self.used_entries as f64 / self.total_entries as f64) * 1000f64).floor() as u16
+            let generation = self.generation();
+            let sample = HASH_FULL_SAMPLE.min(self.total_entries);
+            let used = self
+                .tt
+                .iter()
+                .flat_map(|b| b.bucket.iter())
+                .take(sample)
+                .filter(|e| !e.is_empty() && D::unpack(e.load().1).age() == generation)
+                .count();
+
+            ((used as f64 / sample as f64) * 1000f64).floor() as u16
         } else {
             0
         }
@@ -314,10 +435,9 @@ impl<D: IHashData + Copy + Clone> TT<D> {
 }
 
 // Private functions
-impl<D: IHashData + Copy + Clone> TT<D> {
+impl<D: IHashData> TT<D> {
     // Calculate the index (bucket) where the data is going to be stored.
-    // Use only the upper half of the Zobrist key for this, so the lower
-    // half can be used to calculate a verification.
+    // Use only the upper half of the Zobrist key for this.
     fn calculate_index(&self, zobrist_key: ZobristKey) -> usize {
         let key = (zobrist_key & HIGH_FOUR_BYTES) >> SHIFT_TO_LOWER;
         let total = self.total_buckets as u64;
@@ -325,17 +445,19 @@ impl<D: IHashData + Copy + Clone> TT<D> {
         (key % total) as usize
     }
 
-    // Many positions will end up at the same index, and thus in the same
-    // bucket. Calculate a verification for the position so it can later be
-    // found in the bucket. Use the other half of the Zobrist key for this.
-    fn calculate_verification(&self, zobrist_key: ZobristKey) -> u32 {
-        (zobrist_key & LOW_FOUR_BYTES) as u32
+    fn generation(&self) -> u8 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    // Allocate the buckets one by one, as atomics can't be cloned.
+    fn create_buckets(total_buckets: usize) -> Vec<Bucket> {
+        (0..total_buckets).map(|_| Bucket::new()).collect()
     }
 
     // This function calculates the values for total_buckets and
     // total_entries. These depend on the requested TT size.
     fn calculate_init_values(megabytes: usize) -> (usize, usize) {
-        let entry_size = std::mem::size_of::<Entry<D>>();
+        let entry_size = std::mem::size_of::<Entry>();
         let bucket_size = entry_size * ENTRIES_PER_BUCKET;
         let total_buckets = MEGABYTE / bucket_size * megabytes;
         let total_entries = total_buckets * ENTRIES_PER_BUCKET;
@@ -21,19 +21,32 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-use super::{defs::ErrFatal, Engine};
+use super::{
+    defs::{ErrFatal, ErrNormal},
+    Engine,
+};
 use crate::{
     board::Board,
+    comm::CommControl,
     defs::{EngineRunResult, FEN_KIWIPETE_POSITION},
     misc::parse,
     misc::parse::PotentialMove,
+    misc::{
+        perft,
+        pgn::{self, PgnGame},
+        san,
+    },
     movegen::{
         defs::{Move, MoveList, MoveType},
         MoveGenerator,
     },
+    search::{defs::BenchResult, Search},
 };
 use if_chain::if_chain;
-use std::sync::Mutex;
+use std::{
+    sync::{Mutex, TryLockError},
+    time::Instant,
+};
 
 impl Engine {
     // This function sets up a position using a given FEN-string.
@@ -48,18 +61,23 @@ impl Engine {
         self.board
             .lock()
             .expect(ErrFatal::LOCK)
-            .fen_read(Some(fen))?;
+            .fen_read(Some(fen), &self.mg)?;
 
         Ok(())
     }
 
     // This function executes a move on the internal board, if it legal to
-    // do so in the given position.
+    // do so in the given position. The move can be given in long algebraic
+    // notation ("e2e4") or in SAN ("e4").
     pub fn execute_move(&mut self, m: String) -> bool {
         // Prepare shorthand variables.
-        let empty = (0usize, 0usize, 0usize);
-        let potential_move = parse::algebraic_move_to_number(&m[..]).unwrap_or(empty);
-        let is_pseudo_legal = self.pseudo_legal(potential_move, &self.board, &self.mg);
+        let is_pseudo_legal = match parse::algebraic_move_to_number(&m[..]) {
+            Ok(potential_move) => self.pseudo_legal(potential_move, &self.board, &self.mg),
+            Err(_) => {
+                let board = self.board.lock().expect(ErrFatal::LOCK);
+                san::san_to_move(&board, &self.mg, &m).ok_or(())
+            }
+        };
         let mut is_legal = false;
 
         if let Ok(ips) = is_pseudo_legal {
@@ -68,6 +86,105 @@ impl Engine {
         is_legal
     }
 
+    // Creates a line for each move played so far: the move in SAN, and
+    // the game state of the position it was played in.
+    pub fn history(&self) -> Vec<String> {
+        let board = self.board.lock().expect(ErrFatal::LOCK);
+        let san = san::history_to_san(&board, &self.mg);
+
+        san.iter()
+            .enumerate()
+            .map(|(i, m)| format!("{:<8} {}", m, board.history.get_ref(i).as_string()))
+            .collect()
+    }
+
+    // Creates the PGN of the game played so far. Moves played by the
+    // engine get its score and depth as a comment.
+    pub fn pgn(&self) -> String {
+        let board = self.board.lock().expect(ErrFatal::LOCK);
+        let mut game = PgnGame::from_board(&board, &self.mg);
+
+        for (i, pgn_move) in game.moves.iter_mut().enumerate() {
+            let state = board.history.get_ref(i);
+            if let Some((m, score)) = self.scores.played.get(&state.zobrist_key) {
+                if *m == state.next_move.to_short_move() {
+                    pgn_move.comment = Some(score.clone());
+                }
+            }
+        }
+
+        pgn::write(&game)
+    }
+
+    // Runs perft divide on the current position, returning the number of
+    // leaf nodes per move and the time it took.
+    pub fn divide(&self, depth: i8) -> (Vec<(Move, u64)>, u128) {
+        let board = self.board.lock().expect(ErrFatal::LOCK).clone();
+        let tt = self.tt_perft.read().expect(ErrFatal::LOCK);
+        let tt_enabled = self.settings.tt_size > 0;
+        let threads = self.settings.threads;
+
+        let now = Instant::now();
+        let result = perft::divide(&board, depth, &self.mg, &tt, tt_enabled, threads);
+        (result, now.elapsed().as_millis())
+    }
+
+    // Resizes the search TT. A running search holds on to the TT, and
+    // waiting for it would block the engine thread so it can't handle
+    // "stop". In that case the TT is resized when the search finishes.
+    pub fn resize_tt(&mut self, megabytes: usize) {
+        match self.tt_search.try_write() {
+            Ok(mut tt) => tt.resize(megabytes),
+            Err(TryLockError::WouldBlock) => self.settings.pending_tt_size = Some(megabytes),
+            Err(TryLockError::Poisoned(_)) => panic!("{}", ErrFatal::LOCK),
+        }
+    }
+
+    // Runs the bench, using the engine's TT.
+    pub fn bench(&self, depth: i8) -> BenchResult {
+        let tt = self.tt_search.read().expect(ErrFatal::LOCK);
+        Search::bench(&self.mg, &tt, self.settings.tt_size > 0, depth)
+    }
+
+    // Remembers the score of the move the engine is about to play, in the
+    // current position.
+    pub fn record_score(&mut self, m: Move) {
+        if let Some(score) = self.scores.last.take() {
+            let key = self
+                .board
+                .lock()
+                .expect(ErrFatal::LOCK)
+                .game_state
+                .zobrist_key;
+            self.scores.played.insert(key, (m.to_short_move(), score));
+        }
+    }
+
+    // Converts the moves given with "go searchmoves" into the moves the
+    // search should restrict itself to. Moves that are not possible in
+    // the current position are reported and left out.
+    pub fn search_moves(&self, moves: &[String]) -> Vec<Move> {
+        let mut search_moves: Vec<Move> = Vec::new();
+
+        for m in moves.iter() {
+            let potential_move = parse::algebraic_move_to_number(&m[..]);
+            let is_pseudo_legal = match potential_move {
+                Ok(pm) => self.pseudo_legal(pm, &self.board, &self.mg),
+                Err(_) => Err(()),
+            };
+
+            match is_pseudo_legal {
+                Ok(ips) => search_moves.push(ips),
+                Err(_) => {
+                    let msg = format!("{}: {}", m, ErrNormal::NOT_LEGAL);
+                    self.comm.send(CommControl::InfoString(msg));
+                }
+            }
+        }
+
+        search_moves
+    }
+
     // After the engine receives an incoming move, it checks if this move
     // is actually in the list of pseudo-legal moves for this position.
     pub fn pseudo_legal(
@@ -82,6 +199,7 @@ impl Engine {
         let mut ml = MoveList::new();
         let mtx_board = board.lock().expect(ErrFatal::LOCK);
         mg.generate_moves(&mtx_board, &mut ml, MoveType::All);
+        let chess960 = mtx_board.chess960;
         std::mem::drop(mtx_board);
 
         // Determine if the potential move is pseudo-legal. make() wil
@@ -90,7 +208,7 @@ impl Engine {
             let current = ml.get_move(i);
             if_chain! {
                 if m.0 == current.from();
-                if m.1 == current.to();
+                if m.1 == current.notation_to(chess960);
                 if m.2 == current.promoted();
                 then {
                     result = Ok(current);
@@ -23,18 +23,18 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{
     board::Board,
-    engine::defs::{PerftData, TT},
+    engine::defs::{ErrFatal, PerftData, TT},
     extra::epds::LARGE_TEST_EPDS,
-    misc::{perft, print},
+    misc::{epd, perft, print},
     movegen::MoveGenerator,
 };
 use std::{
-    sync::{Arc, Mutex},
+    sync::{Arc, RwLock},
     time::Instant,
 };
 
-const SEMI_COLON: char = ';';
-const SPACE: char = ' ';
+const DEPTH_PREFIX: char = 'D';
+const COMMENT: char = '#';
 
 const ERR_NONE: usize = 0;
 const ERR_FEN: usize = 1;
@@ -50,60 +50,87 @@ const TEST_RESULTS: [&str; 5] = [
     "Failure: Found leaf nodes not equal to expected value.",
 ];
 
-// This private function is the one actually running tests.
-// This can be the entire suite, or a single test.
-pub fn run(tt: Arc<Mutex<TT<PerftData>>>, tt_enabled: bool) {
-    let number_of_tests = LARGE_TEST_EPDS.len();
+// Short version of the results, for the table at the end.
+const TABLE_RESULTS: [&str; 5] = ["OK", "FEN", "DEPTH", "EXPECT", "FAIL"];
+
+// Runs the perft tests in the given EPD-file, or the built-in suite if
+// no file is given. The root moves are divided over the threads. Depths
+// above max_depth are skipped. A test stops at its first error, but the
+// suite keeps going with the next test. At the end, a table with the
+// results is printed. If any of the tests did not pass, an error is
+// returned.
+pub fn run(
+    file: Option<&str>,
+    max_depth: Option<i8>,
+    tt: Arc<RwLock<TT<PerftData>>>,
+    tt_enabled: bool,
+    threads: usize,
+) -> Result<(), String> {
+    let tt = tt.read().expect(ErrFatal::LOCK);
+    let tests = load_tests(file)?;
+    let number_of_tests = tests.len();
     let move_generator = MoveGenerator::new();
     let mut board: Board = Board::new();
-    let mut result: usize = ERR_NONE;
+    let mut results: Vec<(usize, String)> = Vec::new();
 
     // Run all the tests.
-    let mut test_nr = 0;
-    while (test_nr < number_of_tests) && (result == 0) {
-        // Split the test's data string into multiple parts.
-        let test_data: Vec<String> = LARGE_TEST_EPDS[test_nr]
-            .split(SEMI_COLON)
-            .map(|s| s.trim().to_string())
-            .collect();
-        let fen = &test_data[0];
-
-        // Set up the position according to the provided FEN-string.
-        let setup_result = board.fen_read(Some(fen));
+    for (test_nr, test) in tests.iter().enumerate() {
+        let mut result: usize = ERR_NONE;
+        let mut details = String::from("");
         println!("Test {} from {}", test_nr + 1, number_of_tests);
+
+        // Parse the test's EPD-string, and set up its position.
+        let (fen, operations) = match epd::parse(test) {
+            Ok(e) => (e.fen(), e.operations),
+            Err(e) => {
+                println!("{e}");
+                (String::from(""), Vec::new())
+            }
+        };
+        let setup_result = board.fen_read(Some(&fen), &move_generator);
         println!("FEN: {fen}");
 
         // If setup ok, then print position. Else, print error and continue to the next test.
         match setup_result {
             Ok(()) => print::position(&board, None),
-            Err(_) => result = ERR_FEN,
+            Err(e) => {
+                println!("{e}");
+                details = e.to_string();
+                result = ERR_FEN;
+            }
         };
 
-        // Run all the parts of a test.
-        let mut index: usize = 1;
-        while index < test_data.len() && (result == 0) {
-            // Data index 0 contains the FEN-string, so skip this and
-            // start at index 1 to find the expected leaf nodes per depth.
-
-            // Split "D1 20" into a vector containing "D1" (depth) and "20" (leaf nodes)
-            let depth_ln: Vec<String> = test_data[index]
-                .split(SPACE)
-                .map(|s| s.to_string())
-                .collect();
-
-            let depth = (depth_ln[0][1..]).parse::<u8>().unwrap_or(0) as i8;
-            let expected_ln = depth_ln[1].parse::<u64>().unwrap_or(0);
+        // Run all the parts of a test. Each part is an operation such as
+        // "D1 20": the depth, and the expected number of leaf nodes.
+        let mut index: usize = 0;
+        while index < operations.len() && (result == 0) {
+            let op = &operations[index];
+            let depth = op
+                .opcode
+                .strip_prefix(DEPTH_PREFIX)
+                .and_then(|d| d.parse::<u8>().ok())
+                .unwrap_or(0) as i8;
+            let expected_ln = op
+                .operands
+                .first()
+                .and_then(|ln| ln.parse::<u64>().ok())
+                .unwrap_or(0);
 
             // Abort if depth or expected leaf node parsing fails.
             result = if depth == 0 { ERR_DEPTH } else { result };
             result = if expected_ln == 0 { ERR_EXPECT } else { result };
+            let too_deep = max_depth.is_some_and(|max| depth > max);
 
-            if result == 0 {
+            if result == 0 && !too_deep {
                 print!("Expect for depth {depth}: {expected_ln}");
 
                 // This is the actual perft run for this test and depth.
                 let now = Instant::now();
-                let found_ln = perft::perft(&mut board, depth, &move_generator, &tt, tt_enabled);
+                let found_ln: u64 =
+                    perft::divide(&board, depth, &move_generator, &tt, tt_enabled, threads)
+                        .iter()
+                        .map(|(_, nodes)| nodes)
+                        .sum();
                 let elapsed = now.elapsed().as_millis();
                 let moves_per_second = ((found_ln * 1000) as f64 / elapsed as f64).floor();
                 let is_ok = expected_ln == found_ln;
@@ -113,13 +140,69 @@ pub fn run(tt: Arc<Mutex<TT<PerftData>>>, tt_enabled: bool) {
                 print!(" - Result: {}", if is_ok { "OK" } else { "Fail" });
                 println!(" ({elapsed} ms, {moves_per_second} leaves/sec)");
 
-                result = if !is_ok { ERR_FAIL } else { result };
+                if !is_ok {
+                    result = ERR_FAIL;
+                    details = format!("D{depth}: expected {expected_ln}, found {found_ln}");
+                }
+            }
+
+            if result == ERR_DEPTH || result == ERR_EXPECT {
+                details = format!("{} {}", op.opcode, op.operands.join(" "));
             }
 
             index += 1;
         }
 
         println!("Test {}: {}\n", test_nr + 1, TEST_RESULTS[result]);
-        test_nr += 1;
+        results.push((result, details));
+    }
+
+    print_table(&tests, &results);
+
+    let failed = results.iter().filter(|(r, _)| *r != ERR_NONE).count();
+    if failed > 0 {
+        return Err(format!("{failed} of {number_of_tests} tests did not pass"));
+    }
+
+    Ok(())
+}
+
+// Loads the tests from the given EPD-file, skipping empty lines and
+// lines starting with "#". Without a file, the built-in suite is used.
+fn load_tests(file: Option<&str>) -> Result<Vec<String>, String> {
+    match file {
+        Some(f) => {
+            let text = std::fs::read_to_string(f).map_err(|e| format!("{f}: {e}"))?;
+            Ok(text
+                .lines()
+                .map(|l| l.trim())
+                .filter(|l| !l.is_empty() && !l.starts_with(COMMENT))
+                .map(|l| l.to_string())
+                .collect())
+        }
+        None => Ok(LARGE_TEST_EPDS.iter().map(|t| t.to_string()).collect()),
+    }
+}
+
+fn print_table(tests: &[String], results: &[(usize, String)]) {
+    let passed = results.iter().filter(|(r, _)| *r == ERR_NONE).count();
+
+    println!("{:<6}{:<8}{:<44}Position", "Test", "Result", "Details");
+    println!("{}", "=".repeat(80));
+    for (i, (result, details)) in results.iter().enumerate() {
+        let position = tests[i].split(';').next().unwrap_or("").trim();
+        println!(
+            "{:<6}{:<8}{:<44}{}",
+            i + 1,
+            TABLE_RESULTS[*result],
+            details,
+            position
+        );
     }
+    println!("{}", "=".repeat(80));
+    println!(
+        "Passed: {passed}, Failed: {}, Total: {}",
+        results.len() - passed,
+        results.len()
+    );
 }
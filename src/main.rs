@@ -34,7 +34,6 @@ mod search;
 mod extra;
 
 // use interface::console;
-use defs::ENGINE_RUN_ERRORS;
 use engine::Engine;
 
 fn main() {
@@ -43,6 +42,9 @@ fn main() {
 
     match result {
         Ok(()) => (),
-        Err(e) => println!("Error code {}: {}", e, ENGINE_RUN_ERRORS[e as usize]),
+        Err(e) => {
+            println!("Error: {e}");
+            std::process::exit(1);
+        }
     };
 }
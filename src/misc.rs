@@ -23,6 +23,9 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 pub mod bits;
 pub mod cmdline;
+pub mod epd;
 pub mod parse;
 pub mod perft;
+pub mod pgn;
 pub mod print;
+pub mod san;
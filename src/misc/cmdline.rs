@@ -24,6 +24,7 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 use crate::{
     defs::{About, FEN_START_POSITION},
     engine::defs::EngineOptionDefaults,
+    search::BENCH_DEPTH,
 };
 use clap::{value_parser, Arg, ArgAction, ArgMatches};
 
@@ -42,6 +43,11 @@ impl CmdLineArgs {
     const PERFT_HELP: &'static str = "Run perft to the given depth";
     const PERFT_DEFAULT: i8 = 0;
 
+    // Perft divide
+    const DIVIDE_LONG: &'static str = "divide";
+    const DIVIDE_SHORT: char = 'v';
+    const DIVIDE_HELP: &'static str = "Perft: count leaf nodes per move";
+
     // Interface
     const COMM_LONG: &'static str = "comm";
     const COMM_SHORT: char = 'c';
@@ -49,11 +55,35 @@ impl CmdLineArgs {
     const COMM_VALUES: [&'static str; 2] = ["uci", "xboard"];
     const COMM_DEFAULT: &'static str = "uci";
 
+    // PGN
+    const PGN_LONG: &'static str = "pgn";
+    const PGN_SHORT: char = 'g';
+    const PGN_HELP: &'static str = "Print the positions of the games in a PGN-file";
+
+    // Test suite (tactics)
+    const SUITE_LONG: &'static str = "suite";
+    const SUITE_SHORT: char = 's';
+    const SUITE_HELP: &'static str = "Run the test suite in the given EPD-file";
+
+    const MOVE_TIME_LONG: &'static str = "movetime";
+    const MOVE_TIME_SHORT: char = 'm';
+    const MOVE_TIME_HELP: &'static str = "Test suite: search time per position in ms";
+
+    const DEPTH_LONG: &'static str = "depth";
+    const DEPTH_SHORT: char = 'd';
+    const DEPTH_HELP: &'static str = "Search depth for --suite; maximum depth for --epdtest";
+
+    // Bench
+    const BENCH_COMMAND: &'static str = "bench";
+    const BENCH_HELP: &'static str = "Search the bench positions; print the node count";
+    const BENCH_DEPTH_ARG: &'static str = "depth";
+    const BENCH_DEPTH_HELP: &'static str = "Depth to search each position to";
+
     // Threads
     const THREADS_LONG: &'static str = "threads";
     const THREADS_SHORT: char = 't';
     const THREADS_HELP: &'static str = "Number of CPU-threads to use";
-    const THREADS_DEFAULT: usize = 1;
+    const THREADS_DEFAULT: usize = EngineOptionDefaults::THREADS_DEFAULT;
 
     const HASH_LONG: &'static str = "hash";
     const HASH_SHORT: char = 'h';
@@ -78,7 +108,7 @@ impl CmdLineArgs {
     // Test
     const EPD_TEST_LONG: &'static str = "epdtest";
     const EPD_TEST_SHORT: char = 'e';
-    const EPD_TEST_HELP: &'static str = "Run EPD Test Suite";
+    const EPD_TEST_HELP: &'static str = "Run EPD perft suite (built-in, or from the given file)";
 }
 
 pub struct CmdLine {
@@ -113,6 +143,41 @@ impl CmdLine {
             .unwrap_or(&CmdLineArgs::PERFT_DEFAULT)
     }
 
+    pub fn pgn(&self) -> Option<String> {
+        self.arguments
+            .get_one::<String>(CmdLineArgs::PGN_LONG)
+            .cloned()
+    }
+
+    pub fn suite(&self) -> Option<String> {
+        self.arguments
+            .get_one::<String>(CmdLineArgs::SUITE_LONG)
+            .cloned()
+    }
+
+    pub fn move_time(&self) -> Option<u128> {
+        self.arguments
+            .get_one::<u128>(CmdLineArgs::MOVE_TIME_LONG)
+            .copied()
+    }
+
+    pub fn depth(&self) -> Option<i8> {
+        self.arguments
+            .get_one::<i8>(CmdLineArgs::DEPTH_LONG)
+            .copied()
+    }
+
+    // Returns the depth to run the bench with, if it was requested.
+    pub fn bench(&self) -> Option<i8> {
+        self.arguments
+            .subcommand_matches(CmdLineArgs::BENCH_COMMAND)
+            .map(|bench| {
+                *bench
+                    .get_one::<i8>(CmdLineArgs::BENCH_DEPTH_ARG)
+                    .unwrap_or(&BENCH_DEPTH)
+            })
+    }
+
     pub fn threads(&self) -> usize {
         *self
             .arguments
@@ -127,6 +192,10 @@ impl CmdLine {
             .unwrap_or(&CmdLineArgs::HASH_DEFAULT)
     }
 
+    pub fn has_divide(&self) -> bool {
+        self.arguments.get_flag(CmdLineArgs::DIVIDE_LONG)
+    }
+
     pub fn has_kiwipete(&self) -> bool {
         self.arguments.get_flag(CmdLineArgs::KIWI_LONG)
     }
@@ -142,7 +211,16 @@ impl CmdLine {
 
     #[cfg(feature = "extra")]
     pub fn has_test(&self) -> bool {
-        self.arguments.get_flag(CmdLineArgs::EPD_TEST_LONG)
+        self.arguments.contains_id(CmdLineArgs::EPD_TEST_LONG)
+    }
+
+    // Without a file name, the built-in test suite is used.
+    #[cfg(feature = "extra")]
+    pub fn test_file(&self) -> Option<String> {
+        self.arguments
+            .get_one::<String>(CmdLineArgs::EPD_TEST_LONG)
+            .filter(|f| !f.is_empty())
+            .cloned()
     }
 
     fn get() -> ArgMatches {
@@ -176,6 +254,45 @@ impl CmdLine {
                     .value_parser(value_parser!(i8))
                     .num_args(1),
             )
+            .arg(
+                Arg::new(CmdLineArgs::PGN_LONG)
+                    .short(CmdLineArgs::PGN_SHORT)
+                    .long(CmdLineArgs::PGN_LONG)
+                    .help(CmdLineArgs::PGN_HELP)
+                    .value_parser(value_parser!(String))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::SUITE_LONG)
+                    .short(CmdLineArgs::SUITE_SHORT)
+                    .long(CmdLineArgs::SUITE_LONG)
+                    .help(CmdLineArgs::SUITE_HELP)
+                    .value_parser(value_parser!(String))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::MOVE_TIME_LONG)
+                    .short(CmdLineArgs::MOVE_TIME_SHORT)
+                    .long(CmdLineArgs::MOVE_TIME_LONG)
+                    .help(CmdLineArgs::MOVE_TIME_HELP)
+                    .value_parser(value_parser!(u128))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::DEPTH_LONG)
+                    .short(CmdLineArgs::DEPTH_SHORT)
+                    .long(CmdLineArgs::DEPTH_LONG)
+                    .help(CmdLineArgs::DEPTH_HELP)
+                    .value_parser(value_parser!(i8).range(1..))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::DIVIDE_LONG)
+                    .short(CmdLineArgs::DIVIDE_SHORT)
+                    .long(CmdLineArgs::DIVIDE_LONG)
+                    .help(CmdLineArgs::DIVIDE_HELP)
+                    .action(ArgAction::SetTrue),
+            )
             .arg(
                 Arg::new(CmdLineArgs::THREADS_LONG)
                     .short(CmdLineArgs::THREADS_SHORT)
@@ -199,6 +316,15 @@ impl CmdLine {
                     .help(CmdLineArgs::KIWI_HELP)
                     .action(ArgAction::SetTrue),
             )
+            .subcommand(
+                clap::Command::new(CmdLineArgs::BENCH_COMMAND)
+                    .about(CmdLineArgs::BENCH_HELP)
+                    .arg(
+                        Arg::new(CmdLineArgs::BENCH_DEPTH_ARG)
+                            .help(CmdLineArgs::BENCH_DEPTH_HELP)
+                            .value_parser(value_parser!(i8).range(1..)),
+                    ),
+            )
             .arg(
                 Arg::new(CmdLineArgs::QUIET_LONG)
                     .long(CmdLineArgs::QUIET_LONG)
@@ -221,7 +347,9 @@ impl CmdLine {
                         .short(CmdLineArgs::EPD_TEST_SHORT)
                         .long(CmdLineArgs::EPD_TEST_LONG)
                         .help(CmdLineArgs::EPD_TEST_HELP)
-                        .action(ArgAction::SetTrue),
+                        .value_parser(value_parser!(String))
+                        .num_args(0..=1)
+                        .default_missing_value(""),
                 );
         }
 
@@ -0,0 +1,229 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2021, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// epd.rs parses lines in Extended Position Description format. Such a
+// line starts with the first four fields of a FEN-string, followed by
+// operations. Each operation consists of an opcode and its operands, and
+// ends with a semicolon, as in:
+//
+// 2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+//
+// The half-move clock and full-move number may also be written directly
+// after the four FEN-fields, as in the perft suites. Otherwise, they are
+// taken from the "hmvc" and "fmvn" opcodes if present.
+
+use std::fmt;
+
+pub struct Opcode;
+impl Opcode {
+    pub const BEST_MOVE: &'static str = "bm";
+    pub const AVOID_MOVE: &'static str = "am";
+    pub const ID: &'static str = "id";
+    pub const COMMENT: &'static str = "c0";
+    pub const DEPTH: &'static str = "acd";
+    pub const HALFMOVE_CLOCK: &'static str = "hmvc";
+    pub const FULLMOVE_NUMBER: &'static str = "fmvn";
+}
+
+const NR_OF_POSITION_FIELDS: usize = 4;
+const SEMI_COLON: char = ';';
+const QUOTE: char = '"';
+const COMMENT: char = '#';
+const DEFAULT_HALFMOVE_CLOCK: &str = "0";
+const DEFAULT_FULLMOVE_NUMBER: &str = "1";
+
+#[derive(Debug)]
+pub enum EpdError {
+    Position,
+    Operation,
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            EpdError::Position => "Position incomplete",
+            EpdError::Operation => "Operation incorrect",
+        };
+        write!(f, "EPD: {msg}")
+    }
+}
+
+pub struct EpdOperation {
+    pub opcode: String,
+    pub operands: Vec<String>,
+}
+
+pub struct Epd {
+    pub position: String,
+    pub operations: Vec<EpdOperation>,
+    clocks: Option<(String, String)>,
+}
+
+impl Epd {
+    // Returns the position as a complete FEN-string.
+    pub fn fen(&self) -> String {
+        let (hmvc, fmvn) = match &self.clocks {
+            Some((h, f)) => (h.as_str(), f.as_str()),
+            None => (
+                self.operand(Opcode::HALFMOVE_CLOCK)
+                    .unwrap_or(DEFAULT_HALFMOVE_CLOCK),
+                self.operand(Opcode::FULLMOVE_NUMBER)
+                    .unwrap_or(DEFAULT_FULLMOVE_NUMBER),
+            ),
+        };
+
+        format!("{} {hmvc} {fmvn}", self.position)
+    }
+
+    // Returns the operands of the first operation with the given opcode.
+    pub fn operands(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|op| op.opcode == opcode)
+            .map(|op| op.operands.as_slice())
+    }
+
+    // Returns the first operand of the given opcode.
+    pub fn operand(&self, opcode: &str) -> Option<&str> {
+        self.operands(opcode)
+            .and_then(|o| o.first())
+            .map(|o| o.as_str())
+    }
+}
+
+// Parses one EPD-line.
+pub fn parse(line: &str) -> Result<Epd, EpdError> {
+    let mut rest = line.trim();
+    let mut fields: Vec<&str> = Vec::new();
+
+    // Take the four FEN-fields off the front of the line.
+    for _ in 0..NR_OF_POSITION_FIELDS {
+        let (field, remainder) = split_word(rest);
+        if field.is_empty() {
+            return Err(EpdError::Position);
+        }
+        fields.push(field);
+        rest = remainder;
+    }
+
+    // See if the clocks are written as in a FEN-string.
+    let (hmvc, after_hmvc) = split_word(rest);
+    let (fmvn, after_fmvn) = split_word(after_hmvc);
+    let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    let clocks = if is_number(hmvc) && is_number(fmvn) {
+        rest = after_fmvn;
+        Some((hmvc.to_string(), fmvn.to_string()))
+    } else {
+        None
+    };
+
+    Ok(Epd {
+        position: fields.join(" "),
+        operations: parse_operations(rest)?,
+        clocks,
+    })
+}
+
+// Reads all the EPD-lines in a file. Empty lines and lines starting with
+// "#" are skipped.
+pub fn read_file(file: &str) -> Result<Vec<Epd>, String> {
+    let text = std::fs::read_to_string(file).map_err(|e| format!("{file}: {e}"))?;
+    let mut epds: Vec<Epd> = Vec::new();
+
+    for (nr, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(COMMENT) {
+            continue;
+        }
+
+        let epd = parse(line).map_err(|e| format!("{file}, line {}: {e}", nr + 1))?;
+        epds.push(epd);
+    }
+
+    Ok(epds)
+}
+
+// ===== Private functions =====
+
+// Splits the first word off the text. Returns the word and the rest.
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(i) => (&text[..i], &text[i..]),
+        None => (text, ""),
+    }
+}
+
+// Operations end with a semicolon. Operands are separated by spaces, but
+// a quoted string is one operand, even if it contains spaces or
+// semicolons.
+fn parse_operations(text: &str) -> Result<Vec<EpdOperation>, EpdError> {
+    let mut operations: Vec<EpdOperation> = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::from("");
+    let mut in_quotes = false;
+
+    for c in text.chars() {
+        match c {
+            QUOTE => in_quotes = !in_quotes,
+            _ if in_quotes => word.push(c),
+            SEMI_COLON => {
+                push_word(&mut words, &mut word);
+                if let Some(op) = create_operation(&mut words) {
+                    operations.push(op);
+                }
+            }
+            _ if c.is_whitespace() => push_word(&mut words, &mut word),
+            _ => word.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(EpdError::Operation);
+    }
+
+    // The last operation may lack its semicolon.
+    push_word(&mut words, &mut word);
+    if let Some(op) = create_operation(&mut words) {
+        operations.push(op);
+    }
+
+    Ok(operations)
+}
+
+fn push_word(words: &mut Vec<String>, word: &mut String) {
+    if !word.is_empty() {
+        words.push(std::mem::take(word));
+    }
+}
+
+fn create_operation(words: &mut Vec<String>) -> Option<EpdOperation> {
+    if words.is_empty() {
+        return None;
+    }
+
+    let operands = words.split_off(1);
+    let opcode = words.remove(0);
+
+    Some(EpdOperation { opcode, operands })
+}
@@ -26,24 +26,30 @@ use crate::{
     engine::defs::{ErrFatal, PerftData, TT},
     misc::print,
     movegen::{
-        defs::{MoveList, MoveType},
+        defs::{Move, MoveList, MoveType},
         MoveGenerator,
     },
 };
 use std::{
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    thread,
     time::Instant,
 };
 
 // This function runs perft(), while collecting speed information.
 // It uses iterative deepening, so when running perft(7), it will output
-// the results of perft(1) up to and including perft(7).
+// the results of perft(1) up to and including perft(7). The moves in
+// the root position are divided over the given number of threads.
 pub fn run(
     board: Arc<Mutex<Board>>,
     depth: i8,
     mg: Arc<MoveGenerator>,
-    tt: Arc<Mutex<TT<PerftData>>>,
+    tt: Arc<RwLock<TT<PerftData>>>,
     tt_enabled: bool,
+    threads: usize,
 ) {
     let mut total_time: u128 = 0;
     let mut total_nodes: u64 = 0;
@@ -55,13 +61,16 @@ pub fn run(
     let mtx_board = board.lock().expect(ErrFatal::LOCK);
 
     // Clone the locked board for local use.
-    let mut local_board = mtx_board.clone();
+    let local_board = mtx_board.clone();
 
     // The function now has its own local board. Drop the guard. It is not
     // necessary to keep the lock until perft runs out.
     std::mem::drop(mtx_board);
 
-    println!("Benchmarking perft 1-{depth}:");
+    // Perft only reads the TT's structure; entries are updated atomically.
+    let tt = tt.read().expect(ErrFatal::LOCK);
+
+    println!("Benchmarking perft 1-{depth} (threads: {threads}):");
 
     print::position(&local_board, None);
 
@@ -71,7 +80,10 @@ pub fn run(
         let now = Instant::now();
         let mut leaf_nodes = 0;
 
-        leaf_nodes += perft(&mut local_board, d, &mg, &tt, tt_enabled);
+        leaf_nodes += divide(&local_board, d, &mg, &tt, tt_enabled, threads)
+            .iter()
+            .map(|(_, nodes)| nodes)
+            .sum::<u64>();
 
         // Measure time and speed
         let elapsed = now.elapsed().as_millis();
@@ -84,10 +96,7 @@ pub fn run(
         // Request TT usage. (This is provided permille as per UCI
         // spec, so divide by 10 to get the usage in percents.)
         if tt_enabled {
-            hash_full = format!(
-                ", hash full: {}%",
-                tt.lock().expect(ErrFatal::LOCK).hash_full() as f64 / 10f64
-            );
+            hash_full = format!(", hash full: {}%", tt.hash_full() as f64 / 10f64);
         }
 
         // Print the results.
@@ -102,13 +111,92 @@ pub fn run(
     println!("Execution speed: {final_lnps} leaves/second");
 }
 
+// This function runs perft divide on the given position: it counts the
+// leaf nodes for each legal move separately. If the total differs from
+// a reference engine, this shows which move the error is under.
+pub fn run_divide(
+    board: Arc<Mutex<Board>>,
+    depth: i8,
+    mg: Arc<MoveGenerator>,
+    tt: Arc<RwLock<TT<PerftData>>>,
+    tt_enabled: bool,
+    threads: usize,
+) {
+    let local_board = board.lock().expect(ErrFatal::LOCK).clone();
+    let tt = tt.read().expect(ErrFatal::LOCK);
+
+    println!("Perft divide {depth}:");
+    print::position(&local_board, None);
+
+    let now = Instant::now();
+    let result = divide(&local_board, depth, &mg, &tt, tt_enabled, threads);
+    print::divide(&result, now.elapsed().as_millis(), local_board.chess960);
+}
+
+// Returns each legal move in the position, with the number of leaf
+// nodes it leads to at the given depth. The moves are divided over the
+// threads: each thread takes the next move that has not been counted
+// yet, and counts it on its own copy of the board. All threads share
+// the TT.
+pub fn divide(
+    board: &Board,
+    depth: i8,
+    mg: &MoveGenerator,
+    tt: &TT<PerftData>,
+    tt_enabled: bool,
+    threads: usize,
+) -> Vec<(Move, u64)> {
+    let mut local_board = board.clone();
+    let mut move_list: MoveList = MoveList::new();
+    let mut legal_moves: Vec<Move> = Vec::new();
+
+    mg.generate_moves(board, &mut move_list, MoveType::All);
+
+    for i in 0..move_list.len() {
+        let m = move_list.get_move(i);
+
+        if local_board.make(m, mg) {
+            local_board.unmake();
+            legal_moves.push(m);
+        }
+    }
+
+    let next_move = AtomicUsize::new(0);
+    let leaf_nodes: Vec<AtomicU64> = legal_moves.iter().map(|_| AtomicU64::new(0)).collect();
+
+    thread::scope(|s| {
+        for _ in 0..threads.clamp(1, legal_moves.len().max(1)) {
+            s.spawn(|| {
+                let mut thread_board = board.clone();
+
+                loop {
+                    let i = next_move.fetch_add(1, Ordering::Relaxed);
+                    if i >= legal_moves.len() {
+                        break;
+                    }
+
+                    thread_board.make(legal_moves[i], mg);
+                    let ln = perft(&mut thread_board, depth - 1, mg, tt, tt_enabled);
+                    thread_board.unmake();
+                    leaf_nodes[i].store(ln, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+
+    legal_moves
+        .into_iter()
+        .zip(leaf_nodes.into_iter().map(|ln| ln.into_inner()))
+        .collect()
+}
+
 // This is the actual Perft function. It is public, because it is used by
-// the "testsuite" module.
+// the "testsuite" module. It runs on a single thread.
 pub fn perft(
     board: &mut Board,
     depth: i8,
     mg: &MoveGenerator,
-    tt: &Mutex<TT<PerftData>>,
+    tt: &TT<PerftData>,
     tt_enabled: bool,
 ) -> u64 {
     let mut leaf_nodes: u64 = 0;
@@ -123,11 +211,7 @@ pub fn perft(
     // number of leaf nodes that were previously calculated for it.
     let mut leaf_nodes_tt: Option<u64> = None;
     if tt_enabled {
-        if let Some(data) = tt
-            .lock()
-            .expect(ErrFatal::LOCK)
-            .probe(board.game_state.zobrist_key)
-        {
+        if let Some(data) = tt.probe(board.game_state.zobrist_key) {
             leaf_nodes_tt = data.get(depth);
         };
     }
@@ -157,7 +241,7 @@ pub fn perft(
     // We have calculated the number of leaf nodes for this position.
     // Store this in the TT for later use.
     if tt_enabled {
-        tt.lock().expect(ErrFatal::LOCK).insert(
+        tt.insert(
             board.game_state.zobrist_key,
             PerftData::create(depth, leaf_nodes),
         )
@@ -0,0 +1,597 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2021, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// pgn.rs reads and writes games in Portable Game Notation. The reader
+// parses the tag pairs and the movetext (moves in SAN, move numbers,
+// comments, NAGs, variations and the result) and replays the moves on a
+// board, so the positions of the game become available. Variations are
+// kept as text, but they are not replayed. The writer does the opposite:
+// it turns a game back into PGN.
+
+use crate::{
+    board::{defs::FenError, Board},
+    defs::{Sides, FEN_START_POSITION, MAX_MOVE_RULE},
+    misc::san,
+    movegen::MoveGenerator,
+    search::defs::{CHECKMATE, CHECKMATE_THRESHOLD},
+};
+use std::fmt;
+
+const TAG_FEN: &str = "FEN";
+const TAG_SETUP: &str = "SetUp";
+const TAG_RESULT: &str = "Result";
+const TAG_VARIANT: &str = "Variant";
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+const UNKNOWN: &str = "?";
+const UNKNOWN_DATE: &str = "????.??.??";
+const UNFINISHED: &str = "*";
+const CHESS960: &str = "Chess960";
+const CHESS960_NAMES: [&str; 3] = ["chess960", "chess 960", "fischerandom"];
+const MAX_LINE_LENGTH: usize = 80;
+
+// Suffix annotations and the NAGs they stand for.
+const SUFFIXES: [(&str, u8); 6] = [
+    ("!!", 3),
+    ("??", 4),
+    ("!?", 5),
+    ("?!", 6),
+    ("!", 1),
+    ("?", 2),
+];
+
+#[derive(Debug)]
+pub enum PgnError {
+    Tag,
+    Comment,
+    Variation,
+    Fen(FenError),
+    IllegalMove(String),
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::Tag => write!(f, "PGN: Tag pair incorrect"),
+            PgnError::Comment => write!(f, "PGN: Comment not closed"),
+            PgnError::Variation => write!(f, "PGN: Variation not closed"),
+            PgnError::Fen(e) => write!(f, "PGN: {e}"),
+            PgnError::IllegalMove(m) => write!(f, "PGN: Illegal move {m}"),
+        }
+    }
+}
+
+// A move in the game, with everything written down with it.
+pub struct PgnMove {
+    pub san: String,
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+    pub variations: Vec<String>,
+}
+
+impl PgnMove {
+    pub fn new(san: String) -> Self {
+        Self {
+            san,
+            nags: Vec::new(),
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+// A game consists of its tag pairs, its moves, and the result. The
+// positions are the FEN-strings of the start position and the position
+// after each move.
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub comment: Option<String>,
+    pub moves: Vec<PgnMove>,
+    pub result: String,
+    pub positions: Vec<String>,
+}
+
+impl PgnGame {
+    pub fn new() -> Self {
+        Self {
+            tags: Vec::new(),
+            comment: None,
+            moves: Vec::new(),
+            result: String::from(UNFINISHED),
+            positions: Vec::new(),
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    // Adds the tag, or replaces its value if it already exists.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    // Creates a game out of the moves played on the board, from the
+    // position at the start of the board's history. The seven required
+    // tags are set to "unknown", except for the result. That is only set
+    // if the game has actually ended.
+    pub fn from_board(board: &Board, mg: &MoveGenerator) -> Self {
+        let mut game = PgnGame::new();
+        let mut start = board.clone();
+
+        while start.history.len() > 0 {
+            start.unmake();
+        }
+
+        let result = game_result(board, mg);
+        game.result = String::from(result);
+        for name in SEVEN_TAG_ROSTER {
+            let value = match name {
+                TAG_RESULT => result,
+                "Date" => UNKNOWN_DATE,
+                _ => UNKNOWN,
+            };
+            game.set_tag(name, value);
+        }
+
+        if board.chess960 {
+            game.set_tag(TAG_VARIANT, CHESS960);
+        }
+
+        // A Chess960 game always gets its starting position, even if it
+        // happens to be the one of standard chess.
+        let fen = start.fen_write();
+        if board.chess960 || fen != FEN_START_POSITION {
+            game.set_tag(TAG_SETUP, "1");
+            game.set_tag(TAG_FEN, &fen);
+        }
+
+        game.positions.push(fen);
+        for m in san::history_to_san(board, mg) {
+            game.moves.push(PgnMove::new(m));
+        }
+
+        // Replay the moves to collect the positions.
+        for i in 0..board.history.len() {
+            start.make(board.history.get_ref(i).next_move, mg);
+            game.positions.push(start.fen_write());
+        }
+
+        game
+    }
+}
+
+// Reads all the games in the given PGN-text.
+pub fn read(pgn: &str, mg: &MoveGenerator) -> Result<Vec<PgnGame>, PgnError> {
+    let tokens = tokenize(pgn)?;
+    let mut games: Vec<PgnGame> = Vec::new();
+    let mut game = PgnGame::new();
+    let mut board = Board::new();
+    let mut in_movetext = false;
+
+    for token in tokens {
+        // A tag after the movetext means a new game has started,
+        // even if the previous one had no result.
+        if let Token::Tag(..) = token {
+            if in_movetext {
+                games.push(game);
+                game = PgnGame::new();
+                in_movetext = false;
+            }
+        } else if !in_movetext {
+            board = start_position(&mut game, mg)?;
+            in_movetext = true;
+        }
+
+        match token {
+            Token::Tag(name, value) => game.set_tag(&name, &value),
+
+            // A comment before the first move belongs to the game.
+            Token::Comment(c) => match game.moves.last_mut() {
+                Some(m) => m.comment = Some(join_comments(m.comment.take(), c)),
+                None => game.comment = Some(join_comments(game.comment.take(), c)),
+            },
+
+            Token::Nag(n) => {
+                if let Some(m) = game.moves.last_mut() {
+                    m.nags.push(n);
+                }
+            }
+
+            Token::Variation(v) => {
+                if let Some(m) = game.moves.last_mut() {
+                    m.variations.push(v);
+                }
+            }
+
+            Token::Move(text) => {
+                let (text, nag) = split_suffix(&text);
+                let m = match san::san_to_move(&board, mg, text) {
+                    Some(m) => m,
+                    None => return Err(PgnError::IllegalMove(text.to_string())),
+                };
+
+                // Write the move down in our own SAN, so the written
+                // games are always the same, whatever the input was.
+                let mut pgn_move = PgnMove::new(san::move_to_san(&board, mg, m));
+                pgn_move.nags.extend(nag);
+                game.moves.push(pgn_move);
+
+                board.make(m, mg);
+                game.positions.push(board.fen_write());
+            }
+
+            Token::Result(r) => {
+                game.result = r;
+                games.push(game);
+                game = PgnGame::new();
+                in_movetext = false;
+            }
+        }
+    }
+
+    // The last game may have ended without a result.
+    if in_movetext {
+        games.push(game);
+    }
+
+    Ok(games)
+}
+
+// Writes the game as PGN. Lines of movetext are wrapped so they don't
+// become longer than 80 characters.
+pub fn write(game: &PgnGame) -> String {
+    let mut pgn = String::from("");
+
+    for (name, value) in game.tags.iter() {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        pgn.push_str(&format!("[{name} \"{value}\"]\n"));
+    }
+    pgn.push('\n');
+
+    // Find out how the game starts, to number the moves correctly.
+    let start = game.positions.first().map_or(FEN_START_POSITION, |p| p);
+    let parts: Vec<&str> = start.split(' ').collect();
+    let mut black_to_move = parts.get(1) == Some(&"b");
+    let mut move_number = parts
+        .get(5)
+        .and_then(|n| n.parse::<u16>().ok())
+        .unwrap_or(1);
+
+    let mut words: Vec<String> = Vec::new();
+    let mut needs_number = true;
+
+    if let Some(c) = &game.comment {
+        words.push(format!("{{{c}}}"));
+    }
+
+    for m in game.moves.iter() {
+        if !black_to_move {
+            words.push(format!("{move_number}."));
+        } else if needs_number {
+            words.push(format!("{move_number}..."));
+        }
+
+        words.push(m.san.clone());
+        needs_number = false;
+
+        for n in m.nags.iter() {
+            words.push(format!("${n}"));
+        }
+
+        // After a comment or a variation, a move by black needs its
+        // number again to make clear where it belongs.
+        if let Some(c) = &m.comment {
+            words.push(format!("{{{c}}}"));
+            needs_number = true;
+        }
+
+        for v in m.variations.iter() {
+            words.push(format!("({v})"));
+            needs_number = true;
+        }
+
+        if black_to_move {
+            move_number += 1;
+        }
+        black_to_move = !black_to_move;
+    }
+
+    words.push(game.result.clone());
+
+    // Wrap the words into lines.
+    let mut line = String::from("");
+    for w in words {
+        if !line.is_empty() && line.len() + w.len() + 1 > MAX_LINE_LENGTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&w);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+
+    pgn
+}
+
+// Prints the positions of all the games in the given PGN-file as FEN,
+// one per line, so they can be used as test positions.
+pub fn print_positions(file: &str, mg: &MoveGenerator) {
+    let games = match std::fs::read_to_string(file) {
+        Ok(text) => read(&text, mg),
+        Err(e) => {
+            println!("Error: {file}: {e}");
+            return;
+        }
+    };
+
+    match games {
+        Ok(games) => {
+            for fen in games.iter().flat_map(|g| g.positions.iter()) {
+                println!("{fen}");
+            }
+        }
+        Err(e) => println!("Error: {e}"),
+    }
+}
+
+// Creates the comment for a move played by the engine, with the score
+// in pawns (or "M" with the number of moves to mate) and the depth, as
+// in "+0.35/12". The score is from the engine's point of view.
+pub fn score_comment(cp: i16, depth: i8) -> String {
+    let sign = if cp < 0 { "-" } else { "+" };
+
+    if (cp.abs() >= CHECKMATE_THRESHOLD) && (cp.abs() < CHECKMATE) {
+        let ply = CHECKMATE - cp.abs();
+        let moves = (ply + 1) / 2;
+        format!("{sign}M{moves}/{depth}")
+    } else {
+        format!("{sign}{:.2}/{depth}", (cp.abs() as f32) / 100.0)
+    }
+}
+
+// ===== Private functions =====
+
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    Variation(String),
+    Move(String),
+    Result(String),
+}
+
+// Splits the PGN-text into tokens. Move numbers and escaped lines
+// (starting with "%") are thrown away.
+fn tokenize(pgn: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let chars: Vec<char> = pgn.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let at_line_start = i == 0 || chars[i - 1] == '\n';
+
+        match c {
+            '%' if at_line_start => i = skip_line(&chars, i),
+
+            // Rest-of-line comment
+            ';' => {
+                let end = skip_line(&chars, i);
+                let text: String = chars[i + 1..end].iter().collect();
+                tokens.push(Token::Comment(text.trim().to_string()));
+                i = end;
+            }
+
+            '{' => {
+                let end = find(&chars, i, '}').ok_or(PgnError::Comment)?;
+                let text: String = chars[i + 1..end].iter().collect();
+                tokens.push(Token::Comment(text.trim().to_string()));
+                i = end + 1;
+            }
+
+            '[' => {
+                let end = find_tag_end(&chars, i).ok_or(PgnError::Tag)?;
+                let text: String = chars[i + 1..end].iter().collect();
+                tokens.push(parse_tag(&text)?);
+                i = end + 1;
+            }
+
+            '(' => {
+                let end = find_variation_end(&chars, i).ok_or(PgnError::Variation)?;
+                let text: String = chars[i + 1..end].iter().collect();
+                tokens.push(Token::Variation(text.trim().to_string()));
+                i = end + 1;
+            }
+
+            _ if c.is_whitespace() => i += 1,
+
+            // Anything else is a word: a move number, move, NAG or result.
+            _ => {
+                let end = (i..chars.len())
+                    .find(|&x| chars[x].is_whitespace() || "{}[]();".contains(chars[x]))
+                    .unwrap_or(chars.len());
+                let word: String = chars[i..end].iter().collect();
+                tokens.extend(parse_word(&word));
+                i = end.max(i + 1);
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Words may be glued together, as in "1.e4" or "12...Nf6".
+fn parse_word(word: &str) -> Option<Token> {
+    if RESULTS.contains(&word) {
+        return Some(Token::Result(word.to_string()));
+    }
+
+    if let Some(n) = word.strip_prefix('$') {
+        return n.parse::<u8>().ok().map(Token::Nag);
+    }
+
+    // Only strip digits followed by a dot, so "0-0" is left alone.
+    let digits = word.trim_start_matches(|c: char| c.is_ascii_digit());
+    let text = if digits.starts_with('.') {
+        digits.trim_start_matches('.')
+    } else {
+        word
+    };
+
+    if text.is_empty() {
+        None
+    } else {
+        Some(Token::Move(text.to_string()))
+    }
+}
+
+// Parses the text between "[" and "]", such as: Event "Some Event"
+fn parse_tag(text: &str) -> Result<Token, PgnError> {
+    let text = text.trim();
+    let (name, value) = text.split_once(char::is_whitespace).ok_or(PgnError::Tag)?;
+    let value = value.trim();
+
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return Err(PgnError::Tag);
+    }
+
+    let value = value[1..value.len() - 1]
+        .replace("\\\"", "\"")
+        .replace("\\\\", "\\");
+
+    Ok(Token::Tag(name.to_string(), value))
+}
+
+// Returns the index of the newline ending the line at index i.
+fn skip_line(chars: &[char], i: usize) -> usize {
+    find(chars, i, '\n').unwrap_or(chars.len())
+}
+
+fn find(chars: &[char], i: usize, c: char) -> Option<usize> {
+    (i..chars.len()).find(|&x| chars[x] == c)
+}
+
+// The end of a tag is the first "]" that's not within the quoted value.
+fn find_tag_end(chars: &[char], i: usize) -> Option<usize> {
+    let mut in_quotes = false;
+    let mut x = i + 1;
+
+    while x < chars.len() {
+        match chars[x] {
+            '\\' if in_quotes => x += 1,
+            '"' => in_quotes = !in_quotes,
+            ']' if !in_quotes => return Some(x),
+            _ => (),
+        }
+        x += 1;
+    }
+
+    None
+}
+
+// Variations can be nested, and they can contain comments with brackets
+// in them. The variation ends at the ")" that closes the first "(".
+fn find_variation_end(chars: &[char], i: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut x = i;
+
+    while x < chars.len() {
+        match chars[x] {
+            '{' => x = find(chars, x, '}')?,
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(x);
+                }
+            }
+            _ => (),
+        }
+        x += 1;
+    }
+
+    None
+}
+
+// Splits suffix annotations such as "!" or "?!" from the move, and
+// returns them as a NAG.
+fn split_suffix(text: &str) -> (&str, Option<u8>) {
+    for (suffix, nag) in SUFFIXES {
+        if let Some(m) = text.strip_suffix(suffix) {
+            return (m, Some(nag));
+        }
+    }
+
+    (text, None)
+}
+
+fn join_comments(existing: Option<String>, comment: String) -> String {
+    match existing {
+        Some(e) => format!("{e} {comment}"),
+        None => comment,
+    }
+}
+
+// Sets up the position the game starts in: the one in the FEN-tag, or
+// the normal starting position if there is none.
+fn start_position(game: &mut PgnGame, mg: &MoveGenerator) -> Result<Board, PgnError> {
+    let mut board = Board::new();
+    let fen = game.tag(TAG_FEN).map(|f| f.to_string());
+    board.chess960 = game
+        .tag(TAG_VARIANT)
+        .is_some_and(|v| CHESS960_NAMES.contains(&v.to_lowercase().as_str()));
+
+    board.fen_read(fen.as_deref(), mg).map_err(PgnError::Fen)?;
+    game.positions.push(board.fen_write());
+
+    Ok(board)
+}
+
+// Determines the result if the game is over: by checkmate, stalemate, or
+// the 50-move rule. Otherwise the game is unfinished.
+fn game_result(board: &Board, mg: &MoveGenerator) -> &'static str {
+    let mut board = board.clone();
+    let us = board.us();
+    let in_check = mg.square_attacked(&board, us ^ 1, board.king_square(us));
+    let has_moves = !san::legal_moves(&mut board, mg).is_empty();
+
+    match (has_moves, in_check) {
+        (false, true) if us == Sides::WHITE => RESULTS[1],
+        (false, true) => RESULTS[0],
+        (false, false) => RESULTS[2],
+        _ if board.game_state.halfmove_clock >= MAX_MOVE_RULE => RESULTS[2],
+        _ => UNFINISHED,
+    }
+}
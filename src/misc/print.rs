@@ -28,6 +28,7 @@ use crate::{
     },
     defs::{Bitboard, Castling, NrOf, Sides},
     movegen::defs::{Move, MoveList},
+    search::{defs::BenchResult, Search},
 };
 
 type AsciiBoard = [char; NrOf::SQUARES];
@@ -172,6 +173,84 @@ pub fn castling_as_string(permissions: u8) -> String {
     castling_as_string
 }
 
+// Prints the result of perft divide: the number of leaf nodes for each
+// of the legal moves in the position, and the totals.
+pub fn divide(result: &[(Move, u64)], elapsed: u128, chess960: bool) {
+    let total: u64 = result.iter().map(|(_, nodes)| nodes).sum();
+
+    for (m, nodes) in result.iter() {
+        println!("{}: {nodes}", m.as_string(chess960));
+    }
+
+    println!();
+    println!("Moves: {}", result.len());
+    println!("Nodes: {total}");
+    println!("Time: {elapsed} ms");
+}
+
+// Prints the result of the bench: the nodes searched in each position,
+// and the totals. The total number of nodes is the bench's signature.
+pub fn bench(result: &BenchResult) {
+    for (i, p) in result.positions.iter().enumerate() {
+        println!(
+            "Position {:>2}: {:>10} nodes ({} ms)",
+            i + 1,
+            p.nodes,
+            p.time
+        );
+    }
+
+    println!();
+    println!("Depth: {}", result.depth);
+    println!("Nodes: {}", result.nodes());
+    println!("Time: {} ms", result.time());
+    println!(
+        "NPS: {}",
+        Search::nodes_per_second(result.nodes(), result.time())
+    );
+}
+
+// Prints the moves played so far (in SAN), each followed by the game
+// state of the position the move was played in.
+pub fn history(history: &[String]) {
+    if history.is_empty() {
+        println!("No history available.");
+    }
+
+    for (i, h) in history.iter().enumerate() {
+        println!("{:<3}| ply: {} {}", i, i + 1, h);
+    }
+}
+
+pub fn fen(fen: &str) {
+    println!("{fen}");
+}
+
+pub fn pgn(pgn: &str) {
+    print!("{pgn}");
+}
+
+// Prints the custom commands that can be used in a terminal window, in
+// addition to the commands of the protocol the engine is running.
+pub fn help(protocol: &str) {
+    println!("The engine is in {protocol} communication mode. It supports some custom");
+    println!("non-{protocol} commands to make use through a terminal window easier.");
+    println!("These commands can also be very useful for debugging purposes.");
+    println!();
+    println!("Custom commands");
+    println!("================================================================");
+    println!("help      :   This help information.");
+    println!("board     :   Print the current board state.");
+    println!("history   :   Print a list of past board states.");
+    println!("fen       :   Print the current position as FEN.");
+    println!("pgn       :   Print the game played so far as PGN.");
+    println!("perft <n> :   Print perft divide for the current position.");
+    println!("bench [n] :   Search the bench positions (to depth n).");
+    println!("eval      :   Print evaluation for side to move.");
+    println!("exit      :   Quit/Exit the engine.");
+    println!();
+}
+
 // ===== Printing used for development purposes only =====
 
 // This prints a bitboard (64-bit number) to the screen in an 8x8 grid.
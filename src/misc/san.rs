@@ -0,0 +1,274 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2021, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// san.rs converts moves into Standard Algebraic Notation (SAN), such as
+// "Nbd7", "exd5", "e8=Q+" or "O-O", and parses SAN back into moves. Both
+// need the position the move is played in: SAN only mentions what's
+// needed to tell the move apart from the other legal moves.
+
+use crate::{
+    board::{
+        defs::{Pieces, Wing, PIECE_CHAR_CAPS, SQUARE_NAME},
+        Board,
+    },
+    defs::{Piece, Square},
+    misc::parse,
+    movegen::{
+        defs::{Move, MoveList, MoveType},
+        MoveGenerator,
+    },
+};
+
+const CASTLE_KINGSIDE: &str = "O-O";
+const CASTLE_QUEENSIDE: &str = "O-O-O";
+const CAPTURE: char = 'x';
+const PROMOTION: char = '=';
+const CHECK: char = '+';
+const CHECKMATE: char = '#';
+const ANNOTATIONS: &str = "+#!?";
+
+// Converts a legal move into SAN, for the given position.
+pub fn move_to_san(board: &Board, mg: &MoveGenerator, m: Move) -> String {
+    let mut board = board.clone();
+    let mut san = String::from("");
+
+    if m.castling() {
+        let wing = Board::castling_wing(m.from(), m.to());
+        san.push_str(if wing == Wing::KING {
+            CASTLE_KINGSIDE
+        } else {
+            CASTLE_QUEENSIDE
+        });
+    } else {
+        let is_capture = m.captured() != Pieces::NONE || m.en_passant();
+
+        if m.piece() == Pieces::PAWN {
+            // Pawn captures start with the file the pawn came from.
+            if is_capture {
+                san.push(file_char(m.from()));
+            }
+        } else {
+            san.push_str(PIECE_CHAR_CAPS[m.piece()]);
+            san.push_str(&disambiguation(&mut board, mg, m));
+        }
+
+        if is_capture {
+            san.push(CAPTURE);
+        }
+
+        san.push_str(SQUARE_NAME[m.to()]);
+
+        if m.promoted() != Pieces::NONE {
+            san.push(PROMOTION);
+            san.push_str(PIECE_CHAR_CAPS[m.promoted()]);
+        }
+    }
+
+    // Play the move to see if it gives check or checkmate.
+    if board.make(m, mg) {
+        let us = board.us();
+        let in_check = mg.square_attacked(&board, us ^ 1, board.king_square(us));
+
+        if in_check {
+            let is_mate = legal_moves(&mut board, mg).is_empty();
+            san.push(if is_mate { CHECKMATE } else { CHECK });
+        }
+    }
+
+    san
+}
+
+// Parses SAN into a legal move in the given position. Check, mate and
+// annotation symbols are ignored, and the "=" before the promotion piece
+// may be left out. Returns None if the SAN doesn't describe exactly one
+// legal move.
+pub fn san_to_move(board: &Board, mg: &MoveGenerator, san: &str) -> Option<Move> {
+    let mut board = board.clone();
+    let legal = legal_moves(&mut board, mg);
+    let san = san.trim().trim_end_matches(|c| ANNOTATIONS.contains(c));
+    let san = san.replace('0', "O");
+
+    // Castling is written the same way for every position.
+    if san == CASTLE_KINGSIDE || san == CASTLE_QUEENSIDE {
+        let wing = if san == CASTLE_KINGSIDE {
+            Wing::KING
+        } else {
+            Wing::QUEEN
+        };
+        return legal
+            .into_iter()
+            .find(|m| m.castling() && Board::castling_wing(m.from(), m.to()) == wing);
+    }
+
+    let mut chars: Vec<char> = san.chars().filter(|c| *c != PROMOTION).collect();
+
+    // A promotion ends with the piece the pawn promotes to.
+    let mut promoted = Pieces::NONE;
+    if let Some(p) = chars.last().and_then(|c| piece_from_char(*c)) {
+        promoted = p;
+        chars.pop();
+    }
+
+    // Then there's the to-square.
+    if chars.len() < 2 {
+        return None;
+    }
+    let square: String = chars.split_off(chars.len() - 2).into_iter().collect();
+    let to = parse::algebraic_square_to_number(&square)?;
+
+    // If it starts with a piece letter, it's a piece; otherwise a pawn.
+    let mut piece = Pieces::PAWN;
+    if let Some(p) = chars.first().and_then(|c| piece_from_char(*c)) {
+        piece = p;
+        chars.remove(0);
+    }
+
+    // What's left is the capture sign and the file and/or rank the piece
+    // moves from.
+    let mut from_file: Option<char> = None;
+    let mut from_rank: Option<char> = None;
+    for c in chars.iter() {
+        match c {
+            'a'..='h' => from_file = Some(*c),
+            '1'..='8' => from_rank = Some(*c),
+            &CAPTURE => (),
+            _ => return None,
+        }
+    }
+
+    let mut candidates = legal.into_iter().filter(|m| {
+        !m.castling()
+            && m.piece() == piece
+            && m.to() == to
+            && m.promoted() == promoted
+            && from_file.is_none_or(|f| file_char(m.from()) == f)
+            && from_rank.is_none_or(|r| rank_char(m.from()) == r)
+    });
+
+    // The move must be unambiguous.
+    match (candidates.next(), candidates.next()) {
+        (Some(m), None) => Some(m),
+        _ => None,
+    }
+}
+
+// Converts the moves of the game so far into SAN, starting at the first
+// position in the board's history.
+pub fn history_to_san(board: &Board, mg: &MoveGenerator) -> Vec<String> {
+    let mut board = board.clone();
+    let mut moves: Vec<Move> = Vec::new();
+
+    // Go back to the start of the game, collecting the moves.
+    while board.history.len() > 0 {
+        moves.push(board.history.get_ref(board.history.len() - 1).next_move);
+        board.unmake();
+    }
+
+    // Then replay them, writing each move down before playing it.
+    let mut san_moves: Vec<String> = Vec::new();
+    for m in moves.into_iter().rev() {
+        san_moves.push(move_to_san(&board, mg, m));
+        board.make(m, mg);
+    }
+
+    san_moves
+}
+
+// Converts a line of moves, such as a PV, into SAN. The line starts in the
+// given position.
+pub fn line_to_san(board: &Board, mg: &MoveGenerator, line: &[Move]) -> Vec<String> {
+    let mut board = board.clone();
+    let mut san_moves: Vec<String> = Vec::new();
+
+    for m in line.iter() {
+        san_moves.push(move_to_san(&board, mg, *m));
+        board.make(*m, mg);
+    }
+
+    san_moves
+}
+
+// Generates all the legal moves in the position.
+pub fn legal_moves(board: &mut Board, mg: &MoveGenerator) -> Vec<Move> {
+    let mut ml = MoveList::new();
+    let mut legal: Vec<Move> = Vec::new();
+
+    mg.generate_moves(board, &mut ml, MoveType::All);
+    for i in 0..ml.len() {
+        let m = ml.get_move(i);
+        if board.make(m, mg) {
+            board.unmake();
+            legal.push(m);
+        }
+    }
+
+    legal
+}
+
+// ===== Private functions =====
+
+// If another piece of the same type can move to the same square, SAN adds
+// the file the piece moves from. If that's not enough, it adds the rank.
+// Only if neither is enough, it adds both.
+fn disambiguation(board: &mut Board, mg: &MoveGenerator, m: Move) -> String {
+    let others: Vec<Move> = legal_moves(board, mg)
+        .into_iter()
+        .filter(|x| {
+            x.piece() == m.piece() && x.to() == m.to() && x.from() != m.from() && !x.castling()
+        })
+        .collect();
+
+    let same_file = others
+        .iter()
+        .any(|x| file_char(x.from()) == file_char(m.from()));
+    let same_rank = others
+        .iter()
+        .any(|x| rank_char(x.from()) == rank_char(m.from()));
+
+    match (others.is_empty(), same_file, same_rank) {
+        (true, _, _) => String::from(""),
+        (false, false, _) => file_char(m.from()).to_string(),
+        (false, true, false) => rank_char(m.from()).to_string(),
+        (false, true, true) => String::from(SQUARE_NAME[m.from()]),
+    }
+}
+
+fn file_char(square: Square) -> char {
+    SQUARE_NAME[square].chars().next().unwrap_or('-')
+}
+
+fn rank_char(square: Square) -> char {
+    SQUARE_NAME[square].chars().last().unwrap_or('-')
+}
+
+// SAN uses capital letters for the pieces. (A pawn has no letter.)
+fn piece_from_char(c: char) -> Option<Piece> {
+    match c {
+        'K' => Some(Pieces::KING),
+        'Q' => Some(Pieces::QUEEN),
+        'R' => Some(Pieces::ROOK),
+        'B' => Some(Pieces::BISHOP),
+        'N' => Some(Pieces::KNIGHT),
+        _ => None,
+    }
+}
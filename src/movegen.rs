@@ -26,13 +26,14 @@ pub mod defs;
 mod init;
 mod magics;
 mod movelist;
+mod see;
 
 use crate::{
     board::{
-        defs::{Pieces, Squares, BB_RANKS, BB_SQUARES},
+        defs::{Pieces, Wing, BB_RANKS, BB_SQUARES},
         Board,
     },
-    defs::{Bitboard, Castling, NrOf, Piece, Side, Sides, Square, EMPTY},
+    defs::{Bitboard, NrOf, Piece, Side, Sides, Square, EMPTY},
     misc::bits,
 };
 use defs::{Move, MoveType, Shift};
@@ -96,6 +97,48 @@ impl MoveGenerator {
         }
     }
 
+    // Determines if the move can be played in this position, not taking
+    // into account if the king is left in check. This is used for moves
+    // that don't come from the move generator, such as the move from the
+    // TT, or a killer move found in a different position.
+    pub fn is_pseudo_legal(&self, board: &Board, m: Move) -> bool {
+        let us = board.us();
+        let from = m.from();
+        let is_ours = board.bb_side[us] & BB_SQUARES[from] > 0;
+        if !is_ours || board.piece_list[from] != m.piece() {
+            return false;
+        }
+
+        // Pawn moves and castling have too many special cases to check
+        // by hand. Generate them, and see if the move is among them.
+        if m.castling() || m.piece() == Pieces::PAWN {
+            let mut ml = MoveList::new();
+            if m.castling() {
+                self.castling(board, &mut ml);
+            } else {
+                self.pawns(board, &mut ml, MoveType::All);
+            }
+            return (0..ml.len()).any(|i| ml.get_move(i).get_move() == m.get_move());
+        }
+
+        // A piece move has none of the special flags, must capture
+        // exactly what is on the to-square, and the piece must be able
+        // to reach that square.
+        let to = m.to();
+        let is_special = m.promoted() != Pieces::NONE || m.en_passant() || m.double_step();
+        let is_own_target = board.bb_side[us] & BB_SQUARES[to] > 0;
+        if is_special || is_own_target || board.piece_list[to] != m.captured() {
+            return false;
+        }
+
+        let bb_target = match m.piece() {
+            Pieces::KING | Pieces::KNIGHT => self.get_non_slider_attacks(m.piece(), from),
+            piece => self.get_slider_attacks(piece, from, board.occupancy()),
+        };
+
+        bb_target & BB_SQUARES[to] > 0
+    }
+
     // Return non-slider (King, Knight) attacks for the given square.
     pub fn get_non_slider_attacks(&self, piece: Piece, square: Square) -> Bitboard {
         match piece {
@@ -215,81 +258,74 @@ impl MoveGenerator {
         }
     }
 
+    // Generates castling moves. These are encoded as "king takes rook", so
+    // the same code works for normal chess and Chess960, where the king and
+    // rooks can start on any square of the back rank. The squares between
+    // the king and its destination, and between the rook and its
+    // destination, must be empty (apart from the king and rook
+    // themselves). The king may not be in check, nor pass or land on an
+    // attacked square. Checks discovered by moving the rook are found by
+    // make().
     pub fn castling(&self, board: &Board, list: &mut MoveList) {
         // Create shorthand variables.
         let us = board.us();
         let opponent = board.opponent();
-        let castle_perms_white = (board.game_state.castling & (Castling::WK | Castling::WQ)) > 0;
-        let castle_perms_black = (board.game_state.castling & (Castling::BK | Castling::BQ)) > 0;
         let bb_occupancy = board.occupancy();
-        let mut bb_king = board.get_pieces(Pieces::KING, us);
-        let from = bits::next(&mut bb_king);
-
-        // Generate castling moves for white.
-        if us == Sides::WHITE && castle_perms_white {
-            // Kingside
-            if board.game_state.castling & Castling::WK > 0 {
-                let bb_kingside_blockers = BB_SQUARES[Squares::F1] | BB_SQUARES[Squares::G1];
-                let is_kingside_blocked = (bb_occupancy & bb_kingside_blockers) > 0;
-
-                if !is_kingside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E1)
-                    && !self.square_attacked(board, opponent, Squares::F1)
-                {
-                    let to = BB_SQUARES[from] << 2;
-                    self.add_move(board, Pieces::KING, from, to, list);
-                }
-            }
+        let king = board.king_square(us);
 
-            if board.game_state.castling & Castling::WQ > 0 {
-                // Queenside
-                let bb_queenside_blockers =
-                    BB_SQUARES[Squares::B1] | BB_SQUARES[Squares::C1] | BB_SQUARES[Squares::D1];
-                let is_queenside_blocked = (bb_occupancy & bb_queenside_blockers) > 0;
-
-                if !is_queenside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E1)
-                    && !self.square_attacked(board, opponent, Squares::D1)
-                {
-                    let to = BB_SQUARES[from] >> 2;
-                    self.add_move(board, Pieces::KING, from, to, list);
-                }
+        for wing in [Wing::KING, Wing::QUEEN] {
+            if board.game_state.castling & Board::castling_right(us, wing) == 0 {
+                continue;
             }
-        }
 
-        // Generate castling moves for black.
-        if us == Sides::BLACK && castle_perms_black {
-            // Kingside
-            if board.game_state.castling & Castling::BK > 0 {
-                let bb_kingside_blockers = BB_SQUARES[Squares::F8] | BB_SQUARES[Squares::G8];
-                let is_kingside_blocked = (bb_occupancy & bb_kingside_blockers) > 0;
-
-                if !is_kingside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E8)
-                    && !self.square_attacked(board, opponent, Squares::F8)
-                {
-                    let to = BB_SQUARES[from] << 2;
-                    self.add_move(board, Pieces::KING, from, to, list);
+            let rook = board.castling_rooks[us][wing];
+            let (king_to, rook_to) = Board::castling_destinations(us, wing);
+            let bb_king_and_rook = BB_SQUARES[king] | BB_SQUARES[rook];
+            let bb_must_be_empty = (MoveGenerator::bb_between(king, king_to)
+                | MoveGenerator::bb_between(rook, rook_to))
+                & !bb_king_and_rook;
+            let is_blocked = (bb_occupancy & bb_must_be_empty) > 0;
+
+            // Check the king's square and the squares it passes, up to and
+            // including its destination. (The king may already stand on
+            // its destination in Chess960.)
+            let is_attacked = || {
+                let step: isize = if king_to > king { 1 } else { -1 };
+                let mut square = king as isize;
+                loop {
+                    if self.square_attacked(board, opponent, square as Square) {
+                        return true;
+                    }
+                    if square == king_to as isize {
+                        return false;
+                    }
+                    square += step;
                 }
-            }
+            };
 
-            // Queenside
-            if board.game_state.castling & Castling::BQ > 0 {
-                let bb_queenside_blockers =
-                    BB_SQUARES[Squares::B8] | BB_SQUARES[Squares::C8] | BB_SQUARES[Squares::D8];
-                let is_queenside_blocked = (bb_occupancy & bb_queenside_blockers) > 0;
-
-                if !is_queenside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E8)
-                    && !self.square_attacked(board, opponent, Squares::D8)
-                {
-                    let to = BB_SQUARES[from] >> 2;
-                    self.add_move(board, Pieces::KING, from, to, list);
-                }
+            // Also see if the rook is actually there. This can only fail
+            // if a malformed FEN-string was given.
+            let has_rook = board.get_pieces(Pieces::ROOK, us) & BB_SQUARES[rook] > 0;
+
+            if has_rook && !is_blocked && !is_attacked() {
+                let move_data = Pieces::KING
+                    | king << Shift::FROM_SQ
+                    | rook << Shift::TO_SQ
+                    | Pieces::NONE << Shift::CAPTURE
+                    | Pieces::NONE << Shift::PROMOTION
+                    | 1 << Shift::CASTLING;
+                list.push(Move::new(move_data));
             }
         }
     }
 
+    // Returns a bitboard with all the squares from "a" up to and including
+    // "b", on the same rank.
+    fn bb_between(a: Square, b: Square) -> Bitboard {
+        let (low, high) = if a < b { (a, b) } else { (b, a) };
+        (!EMPTY >> (63 - high)) & (!EMPTY << low)
+    }
+
     // Add the generated moves to the move list.
     pub fn add_move(
         &self,
@@ -316,7 +352,6 @@ impl MoveGenerator {
             };
             let promotion = is_pawn && Board::square_on_rank(to_square, promotion_rank);
             let double_step = is_pawn && ((to_square as i8 - from as i8).abs() == 16);
-            let castling = (piece == Pieces::KING) && ((to_square as i8 - from as i8).abs() == 2);
 
             // Gather all data for this move into one 64-bit integer.
             let mut move_data = (piece)
@@ -324,8 +359,7 @@ impl MoveGenerator {
                 | to_square << Shift::TO_SQ
                 | capture << Shift::CAPTURE
                 | (en_passant as usize) << Shift::EN_PASSANT
-                | (double_step as usize) << Shift::DOUBLE_STEP
-                | (castling as usize) << Shift::CASTLING;
+                | (double_step as usize) << Shift::DOUBLE_STEP;
 
             // Push the move to the piece list...
             if !promotion {
@@ -64,7 +64,7 @@ Storing the "To" square: Shift LEFT 9 bits, then XOR with "data".
 
 pub use super::{magics::Magic, movelist::MoveList};
 use crate::{
-    board::defs::{PIECE_CHAR_SMALL, SQUARE_NAME},
+    board::defs::{Files, PIECE_CHAR_SMALL, SQUARE_NAME},
     defs::{Piece, Square},
 };
 
@@ -148,15 +148,34 @@ impl Move {
         self.data = (self.data & !mask) | v;
     }
 
-    pub fn as_string(&self) -> String {
+    // Castling moves are encoded as "king takes rook". In Chess960 they are
+    // also written that way (for example "e1h1"); otherwise the king's
+    // destination is written ("e1g1").
+    pub fn as_string(&self, chess960: bool) -> String {
         format!(
             "{}{}{}",
             SQUARE_NAME[self.from()],
-            SQUARE_NAME[self.to()],
+            SQUARE_NAME[self.notation_to(chess960)],
             PIECE_CHAR_SMALL[self.promoted()]
         )
     }
 
+    // Returns the to-square as it is written down. This only differs from
+    // to() for castling moves outside of Chess960 mode.
+    pub fn notation_to(&self, chess960: bool) -> Square {
+        if !self.castling() || chess960 {
+            return self.to();
+        }
+
+        let rank = self.from() - (self.from() % 8);
+        let file = if self.to() > self.from() {
+            Files::G
+        } else {
+            Files::C
+        };
+        rank + file
+    }
+
     pub fn to_short_move(self) -> ShortMove {
         ShortMove::new((self.data & MOVE_ONLY) as u32)
     }
@@ -0,0 +1,183 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2021, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// see.rs contains the Static Exchange Evaluation. It calculates the
+// material won or lost by a move, if both sides keep capturing on the
+// destination square, each time with their least valuable piece. A side
+// can stop capturing whenever that is better. When a piece leaves the
+// square it attacked from, sliders behind it may now attack the square:
+// the attackers are looked up again with the new occupancy, so these
+// x-rays are found by the magic slider lookups.
+
+use super::{defs::Move, MoveGenerator};
+use crate::{
+    board::{
+        defs::{Pieces, BB_SQUARES},
+        Board,
+    },
+    defs::{Bitboard, Piece, Side, Sides, Square},
+    evaluation::defs::PIECE_VALUES,
+};
+
+// The longest capture sequence on one square: all 32 pieces.
+const MAX_EXCHANGES: usize = 32;
+
+// Attackers are tried from least to most valuable.
+const ATTACKER_ORDER: [Piece; 6] = [
+    Pieces::PAWN,
+    Pieces::KNIGHT,
+    Pieces::BISHOP,
+    Pieces::ROOK,
+    Pieces::QUEEN,
+    Pieces::KING,
+];
+
+impl MoveGenerator {
+    // Returns the material balance of the exchange started by the move,
+    // from the point of view of the side making it.
+    pub fn see(&self, board: &Board, m: Move) -> i16 {
+        if m.castling() {
+            return 0;
+        }
+
+        let to = m.to();
+        let mut gain = [0i16; MAX_EXCHANGES];
+        let mut occupancy = board.occupancy() ^ BB_SQUARES[m.from()];
+
+        // The first capture. The piece on the square is the one that can
+        // be captured next; after a promotion, that's the new piece.
+        let mut on_square = m.piece();
+        gain[0] = value(m.captured());
+        if m.en_passant() {
+            gain[0] = value(Pieces::PAWN);
+            occupancy ^= BB_SQUARES[to ^ 8];
+        }
+        if m.promoted() != Pieces::NONE {
+            gain[0] += value(m.promoted()) - value(Pieces::PAWN);
+            on_square = m.promoted();
+        }
+
+        // Sliders behind a piece that captures may attack the square next.
+        let bb_diagonal = pieces(board, Pieces::BISHOP) | pieces(board, Pieces::QUEEN);
+        let bb_straight = pieces(board, Pieces::ROOK) | pieces(board, Pieces::QUEEN);
+        let mut attackers = self.attackers_to(board, to, occupancy);
+
+        let mut side = board.opponent();
+        let mut depth = 0;
+        loop {
+            let ours = attackers & board.bb_side[side];
+            if ours == 0 {
+                break;
+            }
+
+            // Find our least valuable attacker.
+            let (piece, square) = least_valuable(board, side, ours);
+
+            // The king can't capture if the square is still defended.
+            if piece == Pieces::KING && attackers & board.bb_side[side ^ 1] > 0 {
+                break;
+            }
+
+            // The gain if this capture is made, assuming the piece on the
+            // square is captured back.
+            depth += 1;
+            gain[depth] = value(on_square) - gain[depth - 1];
+            if depth == MAX_EXCHANGES - 1 {
+                break;
+            }
+
+            // Take the attacker off the board, and look for x-rays behind
+            // it. Pawns, bishops and queens capture diagonally, rooks and
+            // queens in a straight line.
+            occupancy ^= BB_SQUARES[square];
+            attackers &= occupancy;
+            if matches!(piece, Pieces::PAWN | Pieces::BISHOP | Pieces::QUEEN) {
+                let bb = self.get_slider_attacks(Pieces::BISHOP, to, occupancy);
+                attackers |= bb & bb_diagonal & occupancy;
+            }
+            if matches!(piece, Pieces::ROOK | Pieces::QUEEN) {
+                let bb = self.get_slider_attacks(Pieces::ROOK, to, occupancy);
+                attackers |= bb & bb_straight & occupancy;
+            }
+
+            on_square = piece;
+            side ^= 1;
+        }
+
+        // Going back through the sequence, each side chooses between
+        // capturing and not capturing.
+        while depth > 0 {
+            gain[depth - 1] = -((-gain[depth - 1]).max(gain[depth]));
+            depth -= 1;
+        }
+
+        gain[0]
+    }
+
+    // Returns all pieces of both sides that attack the square, given the
+    // occupancy. Pieces not in the occupancy have already captured.
+    fn attackers_to(&self, board: &Board, square: Square, occupancy: Bitboard) -> Bitboard {
+        let queens = pieces(board, Pieces::QUEEN);
+        let bb_rook = self.get_slider_attacks(Pieces::ROOK, square, occupancy);
+        let bb_bishop = self.get_slider_attacks(Pieces::BISHOP, square, occupancy);
+        let bb_knight = self.get_non_slider_attacks(Pieces::KNIGHT, square);
+        let bb_king = self.get_non_slider_attacks(Pieces::KING, square);
+        let bb_white_pawns = self.get_pawn_attacks(Sides::BLACK, square);
+        let bb_black_pawns = self.get_pawn_attacks(Sides::WHITE, square);
+
+        let attackers = (bb_rook & (pieces(board, Pieces::ROOK) | queens))
+            | (bb_bishop & (pieces(board, Pieces::BISHOP) | queens))
+            | (bb_knight & pieces(board, Pieces::KNIGHT))
+            | (bb_king & pieces(board, Pieces::KING))
+            | (bb_white_pawns & board.get_pieces(Pieces::PAWN, Sides::WHITE))
+            | (bb_black_pawns & board.get_pieces(Pieces::PAWN, Sides::BLACK));
+
+        attackers & occupancy
+    }
+}
+
+// Returns the least valuable of the side's pieces in the attackers, and
+// the square it stands on.
+fn least_valuable(board: &Board, side: Side, attackers: Bitboard) -> (Piece, Square) {
+    for piece in ATTACKER_ORDER {
+        let bb = attackers & board.get_pieces(piece, side);
+        if bb > 0 {
+            return (piece, bb.trailing_zeros() as Square);
+        }
+    }
+
+    (Pieces::NONE, 0)
+}
+
+// Returns the pieces of this type for both sides.
+fn pieces(board: &Board, piece: Piece) -> Bitboard {
+    board.get_pieces(piece, Sides::WHITE) | board.get_pieces(piece, Sides::BLACK)
+}
+
+fn value(piece: Piece) -> i16 {
+    if piece == Pieces::NONE {
+        0
+    } else {
+        PIECE_VALUES[piece] as i16
+    }
+}
@@ -24,8 +24,13 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 // search.rs contains the engine's search routine.
 
 mod alpha_beta;
+mod bench;
 pub mod defs;
+mod history;
 mod iter_deep;
+mod lmr;
+mod move_picker;
+mod null_move;
 mod qsearch;
 mod sorting;
 mod time;
@@ -37,13 +42,16 @@ use crate::{
     engine::defs::{SearchData, TT},
     movegen::MoveGenerator,
 };
-use crossbeam_channel::Sender;
+use crossbeam_channel::{Receiver, Sender};
+
+pub use bench::BENCH_DEPTH;
 use defs::{
     SearchControl, SearchInfo, SearchParams, SearchRefs, SearchReport, SearchSummary,
     SearchTerminate,
 };
+use lmr::LmrTable;
 use std::{
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, RwLock},
     thread::{self, JoinHandle},
 };
 
@@ -65,7 +73,7 @@ impl Search {
         report_tx: Sender<Information>, // Used to send information to engine.
         board: Arc<Mutex<Board>>,       // Arc pointer to engine's board.
         mg: Arc<MoveGenerator>,         // Arc pointer to engine's move generator.
-        tt: Arc<Mutex<TT<SearchData>>>,
+        tt: Arc<RwLock<TT<SearchData>>>,
         tt_enabled: bool,
     ) {
         // Set up a channel for incoming commands
@@ -80,6 +88,7 @@ impl Search {
             let arc_board = Arc::clone(&board);
             let arc_mg = Arc::clone(&mg);
             let arc_tt = Arc::clone(&tt);
+            let lmr = LmrTable::new();
             let mut search_params = SearchParams::new();
 
             let mut quit = false;
@@ -98,7 +107,7 @@ impl Search {
                     }
                     SearchControl::Stop => halt = true,
                     SearchControl::Quit => quit = true,
-                    SearchControl::Nothing => (),
+                    SearchControl::PonderHit | SearchControl::Nothing => (),
                 }
 
                 // Search isn't halted and not going to quit.
@@ -108,40 +117,109 @@ impl Search {
                     let mut board = mtx_board.clone();
                     std::mem::drop(mtx_board);
 
-                    // Create a place to put search information
-                    let mut search_info = SearchInfo::new();
+                    // Hold on to the TT while the threads are searching. They
+                    // probe and store without locking it.
+                    let tt_guard = arc_tt.read().expect(ErrFatal::LOCK);
+                    let tt: &TT<SearchData> = &tt_guard;
+                    tt.new_generation();
 
-                    // Create references to all needed information and structures.
-                    let mut search_refs = SearchRefs {
-                        board: &mut board,
-                        mg: &arc_mg,
-                        tt: &arc_tt,
-                        tt_enabled,
-                        search_params: &mut search_params,
-                        search_info: &mut search_info,
-                        control_rx: &control_rx,
-                        report_tx: &t_report_tx,
-                    };
+                    // Lazy SMP: helper threads search the same position on
+                    // their own board, sharing only the transposition table.
+                    // The main thread reports, and its best move is played.
+                    let (best_move, ponder_move, mut terminate) = thread::scope(|s| {
+                        let mut helpers = Vec::new();
 
-                    // Start the search using Iterative Deepening.
-                    let (best_move, terminate) = Search::iterative_deepening(&mut search_refs);
+                        for thread_id in 1..search_params.threads {
+                            let (helper_tx, helper_rx) =
+                                crossbeam_channel::unbounded::<SearchControl>();
+                            let mut helper_board = board.clone();
 
-                    // Inform the engine that the search has finished.
-                    let information = Information::Search(SearchReport::Finished(best_move));
-                    t_report_tx.send(information).expect(ErrFatal::CHANNEL);
+                            // Helpers search the same way as the main
+                            // thread, but without limits of their own: they
+                            // keep searching until the main thread tells
+                            // them to stop. They don't report anything.
+                            let mut helper_params = SearchParams {
+                                depth: None,
+                                move_time: None,
+                                nodes: None,
+                                game_time: None,
+                                infinite: true,
+                                quiet: true,
+                                ponder: false,
+                                ..search_params.clone()
+                            };
+
+                            let (mg, lmr, report_tx) = (&arc_mg, &lmr, &t_report_tx);
+                            s.spawn(move || {
+                                let mut helper_info = SearchInfo::new();
+                                let mut helper_refs = SearchRefs {
+                                    board: &mut helper_board,
+                                    mg,
+                                    lmr,
+                                    tt,
+                                    tt_enabled,
+                                    search_params: &mut helper_params,
+                                    search_info: &mut helper_info,
+                                    control_rx: &helper_rx,
+                                    report_tx,
+                                    thread_id,
+                                };
+
+                                Search::iterative_deepening(&mut helper_refs);
+                            });
 
-                    // If the search was finished due to a Stop or Quit
-                    // command then either halt or quit the search.
-                    match terminate {
-                        SearchTerminate::Stop => {
-                            halt = true;
+                            helpers.push(helper_tx);
                         }
-                        SearchTerminate::Quit => {
-                            halt = true;
-                            quit = true;
+
+                        // Create a place to put search information
+                        let mut search_info = SearchInfo::new();
+
+                        // Create references to all needed information and structures.
+                        let mut search_refs = SearchRefs {
+                            board: &mut board,
+                            mg: &arc_mg,
+                            lmr: &lmr,
+                            tt,
+                            tt_enabled,
+                            search_params: &mut search_params,
+                            search_info: &mut search_info,
+                            control_rx: &control_rx,
+                            report_tx: &t_report_tx,
+                            thread_id: 0,
+                        };
+
+                        // Start the search using Iterative Deepening.
+                        let result = Search::iterative_deepening(&mut search_refs);
+
+                        // The main thread is done, so the helpers are too. A
+                        // helper may already have finished on its own (by
+                        // reaching the maximum depth), so ignore send errors.
+                        for helper_tx in helpers.iter() {
+                            helper_tx.send(SearchControl::Stop).ok();
                         }
-                        SearchTerminate::Nothing => (),
+
+                        result
+                    });
+
+                    // Release the TT, so the engine can resize it while this
+                    // thread waits for the end of the search, or is idle.
+                    std::mem::drop(tt_guard);
+
+                    // An infinite or ponder search may not report its best
+                    // move by itself. Wait until the GUI ends the search.
+                    if search_params.must_wait_for_stop() && terminate == SearchTerminate::Nothing {
+                        terminate = Search::wait_for_ponder_end(&control_rx);
                     }
+
+                    // Inform the engine that the search has finished.
+                    let report = SearchReport::Finished(best_move, ponder_move);
+                    let information = Information::Search(report);
+                    t_report_tx.send(information).expect(ErrFatal::CHANNEL);
+
+                    // The search is done. Halt, and quit if this is the
+                    // reason the search was terminated.
+                    halt = true;
+                    quit = terminate == SearchTerminate::Quit;
                 }
             }
         });
@@ -151,6 +229,18 @@ impl Search {
         self.control_tx = Some(control_tx);
     }
 
+    // Wait for the command that ends an infinite or ponder search. Both
+    // "ponderhit" and "stop" make the engine report its best move.
+    fn wait_for_ponder_end(control_rx: &Receiver<SearchControl>) -> SearchTerminate {
+        loop {
+            match control_rx.recv().expect(ErrFatal::CHANNEL) {
+                SearchControl::PonderHit | SearchControl::Stop => return SearchTerminate::Stop,
+                SearchControl::Quit => return SearchTerminate::Quit,
+                SearchControl::Start(_) | SearchControl::Nothing => (),
+            }
+        }
+    }
+
     // This function is used to send commands into the search thread.
     pub fn send(&self, cmd: SearchControl) {
         if let Some(tx) = &self.control_tx {
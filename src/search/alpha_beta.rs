@@ -23,21 +23,22 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use super::{
     defs::{SearchTerminate, CHECKMATE, CHECK_TERMINATION, DRAW, INF, SEND_STATS, STALEMATE},
+    move_picker::MovePicker,
     Search, SearchRefs,
 };
 use crate::{
     board::defs::Pieces,
     defs::MAX_PLY,
-    engine::defs::{ErrFatal, HashFlag, SearchData},
+    engine::defs::{HashFlag, SearchData},
     evaluation,
-    movegen::defs::{Move, MoveList, MoveType, ShortMove},
+    movegen::defs::{Move, MoveList, ShortMove},
 };
 
 impl Search {
     pub fn alpha_beta(
         mut depth: i8,
         mut alpha: i16,
-        beta: i16,
+        mut beta: i16,
         pv: &mut Vec<Move>,
         refs: &mut SearchRefs,
     ) -> i16 {
@@ -45,6 +46,10 @@ impl Search {
         let is_root = refs.search_info.ply == 0; // At root if no moves were played.
         let mut do_pvs = false; // Used for PVS (Principal Variation Search)
 
+        // When root moves are excluded (MultiPV), the result at the root
+        // is not the real result for the position, so it is not stored.
+        let store_tt = !is_root || refs.search_info.excluded_moves.is_empty();
+
         // Check if termination condition is met.
         if refs.search_info.nodes & CHECK_TERMINATION == 0 {
             Search::check_termination(refs);
@@ -61,6 +66,20 @@ impl Search {
             return evaluation::evaluate_position(refs.board);
         }
 
+        // Mate distance pruning. Even if we mate on the next move, the
+        // score can't be better than a mate found closer to the root. The
+        // same goes for being mated. If the window closes, there is no
+        // need to search this node.
+        if !is_root {
+            let ply = refs.search_info.ply as i16;
+            alpha = alpha.max(-CHECKMATE + ply);
+            beta = beta.min(CHECKMATE - ply - 1);
+
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
         // Determine if we are in check.
         let is_check = refs.mg.square_attacked(
             refs.board,
@@ -89,12 +108,7 @@ impl Search {
 
         // Probe the TT for information.
         if refs.tt_enabled {
-            if let Some(data) = refs
-                .tt
-                .lock()
-                .expect(ErrFatal::LOCK)
-                .probe(refs.board.game_state.zobrist_key)
-            {
+            if let Some(data) = refs.tt.probe(refs.board.game_state.zobrist_key) {
                 let tt_result = data.get(depth, refs.search_info.ply, alpha, beta);
                 tt_value = tt_result.0;
                 tt_move = tt_result.1;
@@ -108,16 +122,21 @@ impl Search {
             }
         }
 
+        // Null move pruning. If we can pass and a reduced search still
+        // fails high, a real move will most likely fail high as well.
+        let is_pv = beta - alpha > 1;
+        if !is_root && !is_pv && !is_check && Search::is_null_move_allowed(depth, refs) {
+            if let Some(score) = Search::null_move(depth, beta, refs) {
+                return score;
+            }
+        }
+
         /*=== Actual searching starts here ===*/
 
-        // Generate the moves in this position
+        // The move picker generates and scores the moves in stages, so
+        // the best move will be searched first.
         let mut legal_moves_found = 0;
-        let mut move_list = MoveList::new();
-        refs.mg
-            .generate_moves(refs.board, &mut move_list, MoveType::All);
-
-        // Do move scoring, so the best move will be searched first.
-        Search::score_moves(&mut move_list, tt_move, refs);
+        let mut picker = MovePicker::new(tt_move, refs);
 
         // After SEND_STATS nodes have been searched, check if the
         // MIN_TIME_STATS has been exceeded; if so, sne dthe current
@@ -135,14 +154,18 @@ impl Search {
         // Holds the best move in the move loop, for storing into the TT.
         let mut best_move: ShortMove = ShortMove::new(0);
 
-        // Iterate over the moves.
-        for i in 0..move_list.len() {
-            // This function finds the best move to test according to the
-            // move scoring, and puts it at the current index of the move
-            // list, so get_move() will get this next.
-            Search::pick_move(&mut move_list, i);
+        // Quiet moves searched without a cutoff, to lower their history.
+        let mut quiets_tried = MoveList::new();
+
+        // Iterate over the moves, as handed out by the move picker.
+        while let Some(current_move) = picker.next(refs) {
+            let is_killer = Search::is_killer(current_move, refs);
+
+            // Skip root moves that are excluded from the search.
+            if is_root && Search::is_excluded_root_move(current_move, refs) {
+                continue;
+            }
 
-            let current_move = move_list.get_move(i);
             let is_legal = refs.board.make(current_move, refs.mg);
 
             // If not legal, skip the move and the rest of the function.
@@ -174,8 +197,32 @@ impl Search {
 
             // If it isn't a draw, we must search.
             if !Search::is_draw(refs) {
+                // Late moves are first searched at a reduced depth. Only
+                // if they beat alpha, they get a full depth search.
+                let mut full_depth = true;
+                if Search::is_lmr_allowed(
+                    depth,
+                    legal_moves_found,
+                    current_move,
+                    is_check,
+                    is_killer,
+                    refs,
+                ) {
+                    let r = refs.lmr.reduction(depth, legal_moves_found);
+                    if r > 0 {
+                        eval_score = -Search::alpha_beta(
+                            depth - 1 - r,
+                            -alpha - 1,
+                            -alpha,
+                            &mut node_pv,
+                            refs,
+                        );
+                        full_depth = eval_score > alpha;
+                    }
+                }
+
                 // Try a PVS if applicable.
-                if do_pvs {
+                if full_depth && do_pvs {
                     eval_score =
                         -Search::alpha_beta(depth - 1, -alpha - 1, -alpha, &mut node_pv, refs);
 
@@ -184,7 +231,7 @@ impl Search {
                         eval_score =
                             -Search::alpha_beta(depth - 1, -beta, -alpha, &mut node_pv, refs);
                     }
-                } else {
+                } else if full_depth {
                     eval_score = -Search::alpha_beta(depth - 1, -beta, -alpha, &mut node_pv, refs);
                 }
             }
@@ -203,28 +250,35 @@ impl Search {
             // Beta cutoff: this move is so good for our opponent, that we
             // do not search any further. Insert into TT and return beta.
             if eval_score >= beta {
-                refs.tt.lock().expect(ErrFatal::LOCK).insert(
-                    refs.board.game_state.zobrist_key,
-                    SearchData::create(
-                        depth,
-                        refs.search_info.ply,
-                        HashFlag::Beta,
-                        beta,
-                        best_move,
-                    ),
-                );
+                if store_tt {
+                    refs.tt.insert(
+                        refs.board.game_state.zobrist_key,
+                        SearchData::create(
+                            depth,
+                            refs.search_info.ply,
+                            HashFlag::Beta,
+                            beta,
+                            best_move,
+                        ),
+                    );
+                }
 
                 // If the move is not a capture but still causes a
                 // beta-cutoff, then store it as a killer move and update
                 // the history heuristics.
                 if current_move.captured() == Pieces::NONE {
                     Search::store_killer_move(current_move, refs);
-                    // Search::update_history_heuristic(current_move, depth, refs);
+                    Search::update_history(current_move, &quiets_tried, depth, refs);
                 }
 
                 return beta;
             }
 
+            // Remember the quiet moves that didn't cause a cutoff.
+            if current_move.captured() == Pieces::NONE {
+                quiets_tried.push(current_move);
+            }
+
             // We found a better move for us.
             if eval_score > alpha {
                 // Save our better evaluation score as alpha.
@@ -255,10 +309,12 @@ impl Search {
 
         // We save the best move we found for us; with an ALPHA flag if we
         // didn't improve alpha, or EXACT if we did raise alpha.
-        refs.tt.lock().expect(ErrFatal::LOCK).insert(
-            refs.board.game_state.zobrist_key,
-            SearchData::create(depth, refs.search_info.ply, hash_flag, alpha, best_move),
-        );
+        if store_tt {
+            refs.tt.insert(
+                refs.board.game_state.zobrist_key,
+                SearchData::create(depth, refs.search_info.ply, hash_flag, alpha, best_move),
+            );
+        }
 
         // We have traversed the entire move list and found the best
         // possible move/eval_score for us.
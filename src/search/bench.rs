@@ -0,0 +1,111 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2021, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// bench.rs searches a fixed list of positions to a fixed depth, on a
+// single thread, starting each position with a cleared TT. Because
+// nothing depends on time, the total number of nodes is always the same
+// for the same build and hash size. If a change to the search changes
+// this number, it changes the way the engine searches.
+
+use super::{
+    defs::{BenchResult, SearchInfo, SearchParams, SearchRefs, SearchStats},
+    lmr::LmrTable,
+    Search,
+};
+use crate::{
+    board::Board,
+    engine::defs::{ErrFatal, Information, SearchData, TT},
+    movegen::MoveGenerator,
+};
+use std::{sync::Arc, time::Instant};
+
+pub const BENCH_DEPTH: i8 = 6;
+
+const BENCH_POSITIONS: [&str; 12] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
+    "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+    "rq3rk1/ppp2ppp/1bnpb3/3N2B1/3NP3/7P/PPPQ1PP1/2KR3R w - - 7 14",
+    "r1bq1r1k/1pp1n1pp/1p1p4/4p2Q/4Pp2/1BNP4/PPP2PPP/3R1RK1 w - - 2 14",
+    "r3r1k1/2p2ppp/p1p1bn2/8/1q2P3/2NPQN2/PPP3PP/R4RK1 b - - 2 15",
+    "r1bbk1nr/pp3p1p/2n5/1N4p1/2Np1B2/8/PPP2PPP/2KR1B1R w kq - 0 13",
+    "r1bq1rk1/ppp1nppp/4n3/3p3Q/3P4/1BP1B3/PP1N2PP/R4RK1 w - - 1 16",
+    "4r1k1/r1q2ppp/ppp2n2/4P3/5Rb1/1N1BQ3/PPP3PP/R5K1 w - - 1 17",
+    "2rqkb1r/ppp2p2/2npb1p1/1N1Nn2p/2P1PP2/8/PP2B1PP/R1BQK2R b KQ - 0 11",
+    "6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/3N4 b - - 0 1",
+];
+
+impl Search {
+    // Runs the bench. Each position is searched with iterative deepening,
+    // exactly as a "go depth" search would be, but without the search
+    // thread: the reports it sends are not used.
+    pub fn bench(
+        mg: &Arc<MoveGenerator>,
+        tt: &TT<SearchData>,
+        tt_enabled: bool,
+        depth: i8,
+    ) -> BenchResult {
+        let (report_tx, _report_rx) = crossbeam_channel::unbounded::<Information>();
+        let (_control_tx, control_rx) = crossbeam_channel::unbounded();
+        let lmr = LmrTable::new();
+        let mut result = BenchResult::new(depth);
+
+        for fen in BENCH_POSITIONS {
+            let mut board = Board::new();
+            board.fen_read(Some(fen), mg).expect(ErrFatal::BENCH);
+            tt.clear();
+
+            let mut search_params = SearchParams {
+                depth: Some(depth),
+                quiet: true,
+                ..SearchParams::new()
+            };
+            let mut search_info = SearchInfo::new();
+            let mut refs = SearchRefs {
+                board: &mut board,
+                mg,
+                lmr: &lmr,
+                tt,
+                tt_enabled,
+                search_params: &mut search_params,
+                search_info: &mut search_info,
+                control_rx: &control_rx,
+                report_tx: &report_tx,
+                thread_id: 0,
+            };
+
+            let now = Instant::now();
+            Search::iterative_deepening(&mut refs);
+            let elapsed = now.elapsed().as_millis();
+
+            let nodes = search_info.nodes;
+            let nps = Search::nodes_per_second(nodes, elapsed);
+            result
+                .positions
+                .push(SearchStats::new(elapsed, nodes, nps, 0));
+        }
+
+        result
+    }
+}
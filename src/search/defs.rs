@@ -1,6 +1,7 @@
+use super::lmr::LmrTable;
 use crate::{
     board::Board,
-    defs::MAX_PLY,
+    defs::{NrOf, Sides, MAX_PLY},
     engine::defs::{Information, SearchData, TT},
     movegen::{
         defs::{Move, ShortMove},
@@ -8,15 +9,14 @@ use crate::{
     },
 };
 use crossbeam_channel::{Receiver, Sender};
-use std::{
-    sync::{Arc, Mutex},
-    time::Instant,
-};
+use std::{sync::Arc, time::Instant};
 
 pub use super::time::OVERHEAD;
 
 pub const INF: i16 = 25_000;
-// pub const ASPIRATION_WINDOW: i16 = 50;
+pub const ASPIRATION_WINDOW: i16 = 25; // Initial window on each side of the score
+pub const ASPIRATION_MAX_WINDOW: i16 = 1_000; // Larger windows are opened fully
+pub const ASPIRATION_MIN_DEPTH: i8 = 4; // First depth searched with a window
 pub const CHECKMATE: i16 = 24_000;
 pub const CHECKMATE_THRESHOLD: i16 = 23_900;
 pub const STALEMATE: i16 = 0;
@@ -26,15 +26,26 @@ pub const SEND_STATS: usize = 0x7FFFF; // 524.287 nodes
 pub const MIN_TIME_STATS: u128 = 2_000; // Minimum time for sending stats
 pub const MIN_TIME_CURR_MOVE: u128 = 1_000; // Minimum time for sending curr_move
 pub const MAX_KILLER_MOVES: usize = 2;
+pub const MAX_HISTORY: i32 = 16_384; // History scores stay within +/- this value
+pub const CONTINUATION_PLIES: usize = 2; // Continuation history for 1 and 2 plies back
+pub const NULL_MOVE_MIN_DEPTH: i8 = 3; // Minimum depth to try a null move
+pub const NULL_MOVE_REDUCTION: i8 = 2; // Base reduction of the null move search
+pub const NULL_MOVE_DEPTH_DIVISOR: i8 = 6; // One more reduction per this many plies
+pub const NULL_MOVE_VERIFY_DEPTH: i8 = 6; // Minimum depth to verify a null move cutoff
 
-pub type SearchResult = (Move, SearchTerminate);
+pub type SearchResult = (Move, Option<Move>, SearchTerminate);
 type KillerMoves = [[ShortMove; MAX_KILLER_MOVES]; MAX_PLY as usize];
-// type HistoryHeuristic = [[[u32; NrOf::SQUARES]; NrOf::PIECE_TYPES]; Sides::BOTH];
+type HistoryHeuristic = [[[i16; NrOf::SQUARES]; NrOf::SQUARES]; Sides::BOTH];
+type CounterMoves = [[[ShortMove; NrOf::SQUARES]; NrOf::PIECE_TYPES]; Sides::BOTH];
+type Continuation = [Vec<i16>; CONTINUATION_PLIES]; // See history.rs for the layout.
+const CONTINUATION_SIZE: usize =
+    Sides::BOTH * NrOf::PIECE_TYPES * NrOf::SQUARES * NrOf::PIECE_TYPES * NrOf::SQUARES;
 
 #[derive(PartialEq)]
 // These commands can be used by the engine thread to control the search.
 pub enum SearchControl {
     Start(SearchParams),
+    PonderHit,
     Stop,
     Quit,
     Nothing,
@@ -48,18 +59,6 @@ pub enum SearchTerminate {
     Nothing, // No command received yet.
 }
 
-// SearchMode lists how the search termination criteria will be evaluated,
-// to see if the search has to be stopped.
-#[derive(PartialEq, Copy, Clone)]
-pub enum SearchMode {
-    Depth,    // Run until requested depth is reached.
-    MoveTime, // Run until 'time per move' is used up.
-    Nodes,    // Run until the number of requested nodes was reached.
-    GameTime, // Search determines when to quit, depending on available time.
-    Infinite, // Run forever, until the 'stop' command is received.
-    Nothing,  // No search mode has been defined.
-}
-
 #[derive(PartialEq, Copy, Clone)]
 pub struct GameTime {
     pub wtime: u128,                // White time on the clock in milliseconds
@@ -89,31 +88,65 @@ impl GameTime {
 
 // This struct holds all the search parameters as set by the engine thread.
 // (These parameters are either default, or provided by the user interface
-// before the game starts.)
-#[derive(PartialEq, Copy, Clone)]
+// before the game starts.) Limits can be combined; the search stops as soon
+// as the first one is reached. Without any limits, the search runs until
+// it is told to stop.
+#[derive(PartialEq, Clone)]
 pub struct SearchParams {
-    pub depth: i8,               // Maximum depth to search to
-    pub move_time: u128,         // Maximum time per move to search
-    pub nodes: usize,            // Maximum number of nodes to search
-    pub game_time: GameTime,     // Time available for entire game
-    pub search_mode: SearchMode, // Defines the mode to search in
-    pub quiet: bool,             // No intermediate search stats updates
+    pub depth: Option<i8>,           // Maximum depth to search to
+    pub move_time: Option<u128>,     // Maximum time per move to search
+    pub nodes: Option<usize>,        // Maximum number of nodes to search
+    pub game_time: Option<GameTime>, // Time available for entire game
+    pub mate: Option<u8>,            // Search for a mate in X moves
+    pub infinite: bool,              // Don't stop until 'stop' is received
+    pub quiet: bool,                 // No intermediate search stats updates
+    pub threads: usize,              // Number of threads to search with
+    pub multi_pv: usize,             // Number of best lines to search for
+    pub ponder: bool,                // Pondering: don't stop on time until ponderhit
+    pub search_moves: Vec<Move>,     // Only search these root moves (if any)
+    pub verification: bool,          // Verify null move cutoffs
+    pub console: bool,               // Running in a terminal: also write the PV in SAN
 }
 
 impl SearchParams {
     pub fn new() -> Self {
         Self {
-            depth: MAX_PLY,
-            move_time: 0,
-            nodes: 0,
-            game_time: GameTime::new(0, 0, 0, 0, None),
-            search_mode: SearchMode::Nothing,
+            depth: None,
+            move_time: None,
+            nodes: None,
+            game_time: None,
+            mate: None,
+            infinite: false,
             quiet: false,
+            threads: 1,
+            multi_pv: 1,
+            ponder: false,
+            search_moves: Vec::new(),
+            verification: false,
+            console: false,
         }
     }
 
     pub fn is_game_time(&self) -> bool {
-        self.search_mode == SearchMode::GameTime
+        self.game_time.is_some()
+    }
+
+    // The depth iterative deepening will not go beyond. A mate in X moves
+    // is found at a depth of at most 2X - 1 plies.
+    pub fn max_depth(&self) -> i8 {
+        let depth = self.depth.unwrap_or(MAX_PLY);
+        let mate_depth = match self.mate {
+            Some(m) => (m as i16 * 2 - 1).clamp(1, MAX_PLY as i16) as i8,
+            None => MAX_PLY,
+        };
+
+        depth.min(mate_depth).min(MAX_PLY)
+    }
+
+    // Infinite and ponder searches may not report a best move on their
+    // own; they wait for 'stop' (or 'ponderhit').
+    pub fn must_wait_for_stop(&self) -> bool {
+        self.infinite || self.ponder
     }
 }
 
@@ -121,16 +154,23 @@ impl SearchParams {
 // search into this struct.
 #[derive(PartialEq)]
 pub struct SearchInfo {
-    start_time: Option<Instant>,    // Time the search started
-    pub depth: i8,                  // Depth currently being searched
-    pub seldepth: i8,               // Maximum selective depth reached
-    pub nodes: usize,               // Nodes searched
-    pub ply: i8,                    // Number of plys from the root
-    pub killer_moves: KillerMoves,  // Killer moves (array; see "type" above)
-    pub last_stats_sent: u128,      // When last stats update was sent
-    pub last_curr_move_sent: u128,  // When last current move was sent
-    pub allocated_time: u128,       // Allotted msecs to spend on move
-    pub terminate: SearchTerminate, // Terminate flag
+    start_time: Option<Instant>,        // Time the search started
+    pub depth: i8,                      // Depth currently being searched
+    pub seldepth: i8,                   // Maximum selective depth reached
+    pub nodes: usize,                   // Nodes searched
+    pub ply: i8,                        // Number of plys from the root
+    pub killer_moves: KillerMoves,      // Killer moves (array; see "type" above)
+    pub history: HistoryHeuristic,      // History heuristic: [side][from][to]
+    pub counter_moves: CounterMoves,    // Countermove: [side][piece][to] of previous move
+    pub continuation: Continuation,     // Continuation history, 1 and 2 plies back
+    pub last_stats_sent: u128,          // When last stats update was sent
+    pub last_curr_move_sent: u128,      // When last current move was sent
+    pub allocated_time: u128,           // Allotted msecs to spend on move
+    pub terminate: SearchTerminate,     // Terminate flag
+    pub excluded_moves: Vec<ShortMove>, // Root moves not to search (MultiPV)
+    pub ponder_hit_at: u128,            // Msecs into the search when ponderhit came
+    pub verifying: bool,                // Running a null move verification search
+    pub fail_low: bool,                 // Current depth failed low at the root
 }
 
 impl SearchInfo {
@@ -142,10 +182,17 @@ impl SearchInfo {
             nodes: 0,
             ply: 0,
             killer_moves: [[ShortMove::new(0); MAX_KILLER_MOVES]; MAX_PLY as usize],
+            history: [[[0; NrOf::SQUARES]; NrOf::SQUARES]; Sides::BOTH],
+            counter_moves: [[[ShortMove::new(0); NrOf::SQUARES]; NrOf::PIECE_TYPES]; Sides::BOTH],
+            continuation: std::array::from_fn(|_| vec![0; CONTINUATION_SIZE]),
             last_stats_sent: 0,
             last_curr_move_sent: 0,
             allocated_time: 0,
             terminate: SearchTerminate::Nothing,
+            excluded_moves: Vec::new(),
+            ponder_hit_at: 0,
+            verifying: false,
+            fail_low: false,
         }
     }
 
@@ -161,37 +208,60 @@ impl SearchInfo {
         }
     }
 
+    // Time used on the clock. When pondering, the engine's clock only
+    // starts running after the ponderhit.
+    pub fn time_used(&self) -> u128 {
+        self.timer_elapsed() - self.ponder_hit_at
+    }
+
     pub fn interrupted(&self) -> bool {
         self.terminate != SearchTerminate::Nothing
     }
 }
 
+// The score in a search summary is exact, or it is a bound because the
+// search fell outside the aspiration window. On a fail high, the score is
+// a lower bound; on a fail low it is an upper bound.
+#[derive(PartialEq, Copy, Clone)]
+pub enum ScoreBound {
+    Exact,
+    Lower,
+    Upper,
+}
+
 // After each completed depth, iterative deepening summarizes the running
 // search results within this struct before sending it to the engine
 // thread. The engine thread will send it to Comm, which will transform the
 // information into UCI/XBoard/Console output and print it to STDOUT.
 #[derive(PartialEq, Clone)]
 pub struct SearchSummary {
-    pub depth: i8,      // depth reached during search
-    pub seldepth: i8,   // Maximum selective depth reached
-    pub time: u128,     // milliseconds
-    pub cp: i16,        // centipawns score
-    pub mate: u8,       // mate in X moves
-    pub nodes: usize,   // nodes searched
-    pub nps: usize,     // nodes per second
-    pub hash_full: u16, // TT use in permille
-    pub pv: Vec<Move>,  // Principal Variation
+    pub depth: i8,         // depth reached during search
+    pub seldepth: i8,      // Maximum selective depth reached
+    pub time: u128,        // milliseconds
+    pub cp: i16,           // centipawns score
+    pub mate: u8,          // mate in X moves
+    pub nodes: usize,      // nodes searched
+    pub nps: usize,        // nodes per second
+    pub hash_full: u16,    // TT use in permille
+    pub multi_pv: usize,   // Line number in MultiPV mode (0 = single PV)
+    pub bound: ScoreBound, // Exact score, or lower/upper bound
+    pub pv: Vec<Move>,     // Principal Variation
+    pub san: Vec<String>,  // Principal Variation in SAN
 }
 
 impl SearchSummary {
-    pub fn pv_as_string(&self) -> String {
+    pub fn pv_as_string(&self, chess960: bool) -> String {
         let mut pv = String::from("");
         for next_move in self.pv.iter() {
-            let m = format!(" {}", next_move.as_string());
+            let m = format!(" {}", next_move.as_string(chess960));
             pv.push_str(&m[..]);
         }
         pv.trim().to_string()
     }
+
+    pub fn pv_as_san(&self) -> String {
+        self.san.join(" ")
+    }
 }
 
 #[derive(PartialEq, Copy, Clone)]
@@ -243,19 +313,55 @@ impl SearchStats {
 pub struct SearchRefs<'a> {
     pub board: &'a mut Board,
     pub mg: &'a Arc<MoveGenerator>,
-    pub tt: &'a Arc<Mutex<TT<SearchData>>>,
+    pub lmr: &'a LmrTable,
+    pub tt: &'a TT<SearchData>,
     pub tt_enabled: bool,
     pub search_params: &'a mut SearchParams,
     pub search_info: &'a mut SearchInfo,
     pub control_rx: &'a Receiver<SearchControl>,
     pub report_tx: &'a Sender<Information>,
+    pub thread_id: usize,
+}
+
+impl SearchRefs<'_> {
+    // Only the main search thread (id 0) reports to the engine. The other
+    // threads are helpers that fill the shared transposition table.
+    pub fn is_main_thread(&self) -> bool {
+        self.thread_id == 0
+    }
+}
+
+// This struct holds the result of the bench: the statistics of the
+// search of each position, and the totals.
+#[derive(PartialEq, Clone)]
+pub struct BenchResult {
+    pub depth: i8,
+    pub positions: Vec<SearchStats>,
+}
+
+impl BenchResult {
+    pub fn new(depth: i8) -> Self {
+        Self {
+            depth,
+            positions: Vec::new(),
+        }
+    }
+
+    pub fn nodes(&self) -> usize {
+        self.positions.iter().map(|p| p.nodes).sum()
+    }
+
+    pub fn time(&self) -> u128 {
+        self.positions.iter().map(|p| p.time).sum()
+    }
 }
 
 // This struct holds all the reports a search can send to the engine.
 #[derive(PartialEq)]
 pub enum SearchReport {
-    Finished(Move),                       // Search done. Contains the best move.
-    SearchSummary(SearchSummary),         // Periodic intermediate results.
+    Finished(Move, Option<Move>), // Search done. Best move and ponder move.
+    SearchSummary(SearchSummary), // Periodic intermediate results.
     SearchCurrentMove(SearchCurrentMove), // Move currently searched.
-    SearchStats(SearchStats),             // General search statistics
+    SearchStats(SearchStats),     // General search statistics
+    InfoString(String),           // Message to pass on to the (G)UI
 }
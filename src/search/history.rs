@@ -0,0 +1,153 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2021, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// history.rs keeps track of quiet moves that caused beta cutoffs, so
+// they can be tried earlier elsewhere in the tree. There are three kinds:
+//
+// - The history heuristic: a score per side, from-square and to-square
+//   (the "butterfly" table).
+// - The countermove: the quiet move that refuted the previous move, per
+//   piece and to-square of that previous move.
+// - Continuation history: a score per move, given the move played one
+//   or two plies earlier.
+//
+// On a beta cutoff, the cutoff move gets a bonus and the quiet moves that
+// were tried before it get a malus. Scores are pulled back towards zero
+// as they grow ("gravity"), so they stay within MAX_HISTORY and recent
+// results count more than old ones.
+
+use super::{
+    defs::{SearchRefs, CONTINUATION_PLIES, MAX_HISTORY},
+    Search,
+};
+use crate::{
+    board::{defs::Pieces, Board},
+    defs::{NrOf, Piece, Side},
+    movegen::defs::{Move, MoveList, ShortMove},
+};
+
+// The bonus grows with the depth of the cutoff, up to this maximum.
+const MAX_HISTORY_BONUS: i32 = 1_200;
+
+pub type PreviousMoves = [Option<Move>; CONTINUATION_PLIES];
+
+impl Search {
+    // Updates the history tables after the quiet move caused a cutoff.
+    pub fn update_history(cutoff: Move, quiets_tried: &MoveList, depth: i8, refs: &mut SearchRefs) {
+        let bonus = (depth as i32 * depth as i32).min(MAX_HISTORY_BONUS);
+        let previous = Search::previous_moves(refs.board);
+
+        for i in 0..quiets_tried.len() {
+            Search::apply_history(quiets_tried.get_move(i), &previous, -bonus, refs);
+        }
+        Search::apply_history(cutoff, &previous, bonus, refs);
+
+        // The cutoff move refutes the move played before it.
+        if let Some(p) = previous[0] {
+            let side = refs.board.opponent();
+            refs.search_info.counter_moves[side][moved_piece(p)][p.to()] = cutoff.to_short_move();
+        }
+    }
+
+    // Returns the combined history of the quiet move.
+    pub fn history_score(m: Move, previous: &PreviousMoves, refs: &SearchRefs) -> i32 {
+        let us = refs.board.us();
+        let mut score = refs.search_info.history[us][m.from()][m.to()] as i32;
+
+        for (ply, p) in previous.iter().enumerate() {
+            if let Some(p) = p {
+                let index = continuation_index(us, *p, m);
+                score += refs.search_info.continuation[ply][index] as i32;
+            }
+        }
+
+        score
+    }
+
+    // Returns the move that refuted the previous move the last time.
+    pub fn counter_move(previous: &PreviousMoves, refs: &SearchRefs) -> ShortMove {
+        match previous[0] {
+            Some(p) => {
+                let side = refs.board.opponent();
+                refs.search_info.counter_moves[side][moved_piece(p)][p.to()]
+            }
+            None => ShortMove::new(0),
+        }
+    }
+
+    // Returns the moves played one and two plies ago, if any. A null move
+    // has no piece, so it doesn't count.
+    pub fn previous_moves(board: &Board) -> PreviousMoves {
+        let mut previous: PreviousMoves = [None; CONTINUATION_PLIES];
+        let len = board.history.len();
+
+        for (ply, p) in previous.iter_mut().enumerate() {
+            if len > ply {
+                let m = board.history.get_ref(len - 1 - ply).next_move;
+                if m.get_move() != 0 {
+                    *p = Some(m);
+                }
+            }
+        }
+
+        previous
+    }
+
+    fn apply_history(m: Move, previous: &PreviousMoves, bonus: i32, refs: &mut SearchRefs) {
+        let us = refs.board.us();
+        let info = &mut refs.search_info;
+        apply_bonus(&mut info.history[us][m.from()][m.to()], bonus);
+
+        for (ply, p) in previous.iter().enumerate() {
+            if let Some(p) = p {
+                let index = continuation_index(us, *p, m);
+                apply_bonus(&mut info.continuation[ply][index], bonus);
+            }
+        }
+    }
+}
+
+// The bonus is scaled down as the score approaches MAX_HISTORY.
+fn apply_bonus(entry: &mut i16, bonus: i32) {
+    let current = *entry as i32;
+    *entry = (current + bonus - current * bonus.abs() / MAX_HISTORY) as i16;
+}
+
+// The continuation history is indexed by the side to move, the piece and
+// to-square of the previous move, and the piece and to-square of the move.
+fn continuation_index(side: Side, previous: Move, m: Move) -> usize {
+    let previous_index = moved_piece(previous) * NrOf::SQUARES + previous.to();
+    let move_index = m.piece() * NrOf::SQUARES + m.to();
+    let size = NrOf::PIECE_TYPES * NrOf::SQUARES;
+
+    (side * size + previous_index) * size + move_index
+}
+
+// After a promotion, the promoted piece stands on the to-square.
+fn moved_piece(m: Move) -> Piece {
+    if m.promoted() != Pieces::NONE {
+        m.promoted()
+    } else {
+        m.piece()
+    }
+}
@@ -22,103 +22,240 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 use super::{
-    defs::{SearchMode, SearchRefs, SearchResult, INF},
+    defs::{
+        ScoreBound, SearchRefs, SearchResult, ASPIRATION_MAX_WINDOW, ASPIRATION_MIN_DEPTH,
+        ASPIRATION_WINDOW, CHECKMATE, CHECKMATE_THRESHOLD, INF,
+    },
     ErrFatal, Information, Search, SearchReport, SearchSummary,
 };
-use crate::{defs::MAX_PLY, movegen::defs::Move};
+use crate::{defs::MAX_PLY, misc::san, movegen::defs::Move};
 
 // Actual search routines.
 impl Search {
     pub fn iterative_deepening(refs: &mut SearchRefs) -> SearchResult {
-        // Working variables
-        let mut depth = 1;
+        // Working variables. Half of the helper threads start one ply
+        // deeper, so the threads don't all search the same depth at the
+        // same time.
+        let mut depth = 1 + (refs.thread_id % 2) as i8;
         let mut best_move = Move::new(0);
-        let mut root_pv: Vec<Move> = Vec::new();
+        let mut ponder_move: Option<Move> = None;
         let mut stop = false;
+        let mut mate_found = false;
         let is_game_time = refs.search_params.is_game_time();
 
-        // Determine available time in case of GameTime search mode.
-        if is_game_time {
-            // Determine the maximum time slice available for this move.
-            let time_slice = Search::calculate_time_slice(refs);
-
-            // Experience reveals that after using about 40-50% of the
-            // available time, the next depth will not be finished, so
-            // don't allocated more than 40% of the calculated move time.
-            let factor = 0.40;
-
-            // If we have time, do a normal search in GameTime mode.
-            if time_slice > 0 {
-                // Determine the actual time to allot for this search.
-                refs.search_info.allocated_time = (time_slice as f64 * factor).round() as u128;
-            } else {
-                // We have no time. Send the best move from ply 1 to avoid
-                // killing ourselves by sending no move at all. Change mode
-                // to "depth" and set it to 1 ply.
-                refs.search_params.search_mode = SearchMode::Depth;
-                refs.search_params.depth = 1;
-            }
+        // Determine available time in case of GameTime search mode. While
+        // pondering, the engine's clock isn't running yet; the time is
+        // determined when ponderhit comes in.
+        if is_game_time && !refs.search_params.ponder && !Search::allocate_time(refs) {
+            // We have no time. Send the best move from ply 1 to avoid
+            // killing ourselves by sending no move at all. Drop the
+            // game time and search to a depth of 1 ply.
+            refs.search_params.game_time = None;
+            refs.search_params.depth = Some(1);
         }
 
-        // Set the starting values for alpha and beta, for use with the
-        // aspiration window. We always start with a fully open window.
-        let alpha: i16 = -INF;
-        let beta: i16 = INF;
+        // Score of the previous depth, around which the aspiration window
+        // is set. There is none before the first depth is finished.
+        let mut previous: Option<i16> = None;
 
         // Start the search
         refs.search_info.timer_start();
-        while (depth <= MAX_PLY) && (depth <= refs.search_params.depth) && !stop {
+        while (depth <= MAX_PLY) && (depth <= refs.search_params.max_depth()) && !stop {
             // Set the current depth
             refs.search_info.depth = depth;
 
-            // Get the evaluation for this depth.
-            let eval = Search::alpha_beta(depth, alpha, beta, &mut root_pv, refs);
+            // In MultiPV mode, the root is searched once for each line.
+            // Every next search excludes the root moves found so far.
+            let multi_pv = refs.search_params.multi_pv;
+            refs.search_info.excluded_moves.clear();
 
-            // Create summary if search was not interrupted.
-            if !refs.search_info.interrupted() {
-                // Save the best move until now.
-                if !root_pv.is_empty() {
-                    best_move = root_pv[0];
-                }
+            for line in 0..multi_pv {
+                let mut line_pv: Vec<Move> = Vec::new();
 
-                // Create search summary for this depth.
-                let elapsed = refs.search_info.timer_elapsed();
-                let nodes = refs.search_info.nodes;
-                let hash_full = refs.tt.lock().expect(ErrFatal::LOCK).hash_full();
-                let summary = SearchSummary {
-                    depth,
-                    seldepth: refs.search_info.seldepth,
-                    time: elapsed,
-                    cp: eval,
-                    mate: 0,
-                    nodes,
-                    nps: Search::nodes_per_second(nodes, elapsed),
-                    hash_full,
-                    pv: root_pv.clone(),
+                // Get the evaluation for this depth. In MultiPV mode, the
+                // lines have different scores, so the window is not used.
+                let eval = if multi_pv == 1 {
+                    Search::aspiration_search(depth, previous, &mut line_pv, refs)
+                } else {
+                    Search::alpha_beta(depth, -INF, INF, &mut line_pv, refs)
                 };
 
-                // Create information for the engine
-                let report = SearchReport::SearchSummary(summary);
-                let information = Information::Search(report);
-                refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
+                // Don't use the results of an interrupted search, and stop
+                // if there are no more root moves left to search.
+                if refs.search_info.interrupted() || (line > 0 && line_pv.is_empty()) {
+                    break;
+                }
+
+                // Save the best move until now, and the move we expect
+                // the opponent to reply with.
+                if line == 0 && !line_pv.is_empty() {
+                    best_move = line_pv[0];
+                    ponder_move = line_pv.get(1).copied();
+                }
+
+                if let Some(m) = line_pv.first() {
+                    refs.search_info.excluded_moves.push(m.to_short_move());
+                }
 
-                // Search one ply deepr.
+                // When searching for a mate, stop as soon as one is found.
+                if line == 0 {
+                    mate_found = Search::is_requested_mate(eval, refs);
+                    previous = Some(eval);
+                }
+
+                // Send the search summary for this line.
+                let line_nr = if multi_pv > 1 { line + 1 } else { 0 };
+                Search::send_summary(eval, ScoreBound::Exact, line_nr, line_pv, refs);
+            }
+
+            // Search one ply deeper if this depth was completed.
+            refs.search_info.excluded_moves.clear();
+            if !refs.search_info.interrupted() {
                 depth += 1;
             }
 
-            // Determine if time is up, when in GameTime mode.
-            let time_up = if is_game_time {
-                refs.search_info.timer_elapsed() > refs.search_info.allocated_time
+            // Determine if time is up, when in GameTime mode. While
+            // pondering, the engine's clock isn't running.
+            let time_up = if is_game_time && !refs.search_params.ponder {
+                refs.search_info.time_used() > refs.search_info.allocated_time
             } else {
                 false
             };
 
             // Stop deepening the search if the current depth was
-            // interrupted, or if the time is up.
-            stop = refs.search_info.interrupted() || time_up;
+            // interrupted, if the time is up, or if the requested mate
+            // was found.
+            stop = refs.search_info.interrupted() || time_up || mate_found;
+        }
+
+        // If the mate search was completed without finding the mate, then
+        // there is no mate within the requested number of moves.
+        if let Some(n) = refs.search_params.mate {
+            if !mate_found && !refs.search_info.interrupted() && refs.is_main_thread() {
+                let msg = format!("no mate in {n} found");
+                let information = Information::Search(SearchReport::InfoString(msg));
+                refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
+            }
         }
 
         // Search is done. Report best move and reason to terminate.
-        (best_move, refs.search_info.terminate)
+        (best_move, ponder_move, refs.search_info.terminate)
+    }
+
+    // Searches the root with a narrow window around the score of the
+    // previous depth. Most of the time the score stays within the window,
+    // and the narrow window causes more cutoffs. If the score falls
+    // outside, the window is widened on that side, step by step, and the
+    // root is searched again.
+    fn aspiration_search(
+        depth: i8,
+        previous: Option<i16>,
+        pv: &mut Vec<Move>,
+        refs: &mut SearchRefs,
+    ) -> i16 {
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = match previous {
+            Some(score) if depth >= ASPIRATION_MIN_DEPTH && score.abs() < CHECKMATE_THRESHOLD => {
+                (score - delta, score + delta)
+            }
+            _ => (-INF, INF),
+        };
+
+        refs.search_info.fail_low = false;
+        loop {
+            pv.clear();
+            let eval = Search::alpha_beta(depth, alpha, beta, pv, refs);
+
+            if refs.search_info.interrupted() {
+                return eval;
+            }
+
+            // Widen the window on the side the score fell out of. A fail
+            // low means the best move is in trouble, so the time manager
+            // is told to allow more time.
+            delta = delta.saturating_mul(2);
+            if eval <= alpha && alpha > -INF {
+                refs.search_info.fail_low = true;
+                Search::send_bound(eval, ScoreBound::Upper, refs);
+                alpha = Search::widen(alpha, -delta);
+            } else if eval >= beta && beta < INF {
+                Search::send_bound(eval, ScoreBound::Lower, refs);
+                beta = Search::widen(beta, delta);
+            } else {
+                return eval;
+            }
+        }
+    }
+
+    // Moves a bound of the window. If the window becomes too large, the
+    // bound is opened completely.
+    fn widen(bound: i16, delta: i16) -> i16 {
+        if delta.abs() > ASPIRATION_MAX_WINDOW {
+            INF * delta.signum()
+        } else {
+            (bound + delta).clamp(-INF, INF)
+        }
+    }
+
+    // Bounds are only reported to the (G)UI, as they are not results.
+    fn send_bound(eval: i16, bound: ScoreBound, refs: &SearchRefs) {
+        if !refs.search_params.quiet {
+            Search::send_summary(eval, bound, 0, Vec::new(), refs);
+        }
+    }
+
+    // Sends a search summary to the engine. Helper threads don't report.
+    fn send_summary(
+        eval: i16,
+        bound: ScoreBound,
+        multi_pv: usize,
+        pv: Vec<Move>,
+        refs: &SearchRefs,
+    ) {
+        if !refs.is_main_thread() {
+            return;
+        }
+
+        // The board is in the root position, so the PV can be written
+        // down in SAN for output in a terminal window. A GUI doesn't need
+        // it, so don't spend time on it if there is no terminal.
+        let elapsed = refs.search_info.timer_elapsed();
+        let nodes = refs.search_info.nodes;
+        let pv_san = if refs.search_params.console {
+            san::line_to_san(refs.board, refs.mg, &pv)
+        } else {
+            Vec::new()
+        };
+        let summary = SearchSummary {
+            depth: refs.search_info.depth,
+            seldepth: refs.search_info.seldepth,
+            time: elapsed,
+            cp: eval,
+            mate: 0,
+            nodes,
+            nps: Search::nodes_per_second(nodes, elapsed),
+            hash_full: refs.tt.hash_full(),
+            multi_pv,
+            bound,
+            pv,
+            san: pv_san,
+        };
+
+        // Create information for the engine
+        let report = SearchReport::SearchSummary(summary);
+        let information = Information::Search(report);
+        refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
+    }
+
+    // Returns true if the evaluation is a mate within the number of moves
+    // requested by "go mate".
+    fn is_requested_mate(eval: i16, refs: &SearchRefs) -> bool {
+        match refs.search_params.mate {
+            Some(n) if eval >= CHECKMATE_THRESHOLD => {
+                let moves = (CHECKMATE - eval + 1) / 2;
+                moves <= n as i16
+            }
+            _ => false,
+        }
     }
 }
@@ -0,0 +1,118 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2021, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// lmr.rs contains Late Move Reductions. Because of the move ordering, a
+// cutoff is most likely to come from one of the first moves. Quiet moves
+// late in the list are searched with a reduced depth and a null window.
+// If such a move unexpectedly beats alpha, it is searched again at full
+// depth. The reduction grows with both the depth and the move number.
+
+use super::{
+    defs::{SearchRefs, MAX_KILLER_MOVES},
+    Search,
+};
+use crate::{board::defs::Pieces, defs::MAX_PLY, movegen::defs::Move};
+
+const LMR_MIN_DEPTH: i8 = 3; // Minimum depth to reduce at
+const LMR_MIN_MOVES: u8 = 4; // Number of moves to search before reducing
+const LMR_MAX_MOVES: usize = 64; // Later moves get the same reduction
+const LMR_BASE: f64 = 0.75;
+const LMR_DIVISOR: f64 = 2.25;
+
+type Reductions = [[i8; LMR_MAX_MOVES]; MAX_PLY as usize + 1];
+
+// The reductions are calculated once, when the search thread is created.
+pub struct LmrTable {
+    reductions: Box<Reductions>,
+}
+
+impl LmrTable {
+    pub fn new() -> Self {
+        let mut reductions = Box::new([[0; LMR_MAX_MOVES]; MAX_PLY as usize + 1]);
+
+        for (depth, row) in reductions.iter_mut().enumerate().skip(1) {
+            for (move_nr, r) in row.iter_mut().enumerate().skip(1) {
+                let ln = (depth as f64).ln() * (move_nr as f64).ln();
+                *r = (LMR_BASE + ln / LMR_DIVISOR) as i8;
+            }
+        }
+
+        Self { reductions }
+    }
+
+    // Returns the reduction for the given depth and move number. At least
+    // one ply of depth is left to search.
+    pub fn reduction(&self, depth: i8, move_nr: u8) -> i8 {
+        let d = (depth.max(0) as usize).min(MAX_PLY as usize);
+        let m = (move_nr as usize).min(LMR_MAX_MOVES - 1);
+
+        self.reductions[d][m].clamp(0, (depth - 2).max(0))
+    }
+}
+
+impl Search {
+    // Determines if the move just made may be searched with a reduced
+    // depth. Captures, promotions and killers are not reduced, and neither
+    // are moves that evade or give check. Root moves are never reduced.
+    pub fn is_lmr_allowed(
+        depth: i8,
+        move_nr: u8,
+        m: Move,
+        is_check: bool,
+        is_killer: bool,
+        refs: &SearchRefs,
+    ) -> bool {
+        let is_quiet = m.captured() == Pieces::NONE && m.promoted() == Pieces::NONE;
+
+        // The move has already been made, so the root moves are at ply 1.
+        let is_root_move = refs.search_info.ply <= 1;
+
+        depth >= LMR_MIN_DEPTH
+            && move_nr > LMR_MIN_MOVES
+            && !is_root_move
+            && is_quiet
+            && !is_check
+            && !is_killer
+            // A reduced search can miss the mate "go mate" has to prove
+            // or refute.
+            && refs.search_params.mate.is_none()
+            && !Search::gives_check(refs)
+    }
+
+    // Checks if the move just made puts the opponent in check.
+    fn gives_check(refs: &SearchRefs) -> bool {
+        refs.mg.square_attacked(
+            refs.board,
+            refs.board.opponent(),
+            refs.board.king_square(refs.board.us()),
+        )
+    }
+
+    // Determines if the move is one of the killers at the current ply.
+    pub fn is_killer(m: Move, refs: &SearchRefs) -> bool {
+        let ply = refs.search_info.ply as usize;
+        let killers = &refs.search_info.killer_moves[ply];
+
+        (0..MAX_KILLER_MOVES).any(|n| killers[n].get_move() == m.get_move())
+    }
+}
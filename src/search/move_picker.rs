@@ -0,0 +1,222 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2021, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// move_picker.rs hands out the moves of a position to alpha-beta one by
+// one, in stages, and only generates moves when they are needed. Often
+// the first move causes a beta cutoff; then the other moves are never
+// generated or scored. The stages are:
+//
+// 1. The TT move. It comes from a different position with the same
+//    Zobrist key (or a collision), so it is checked first.
+// 2. Captures that don't lose material, by MVV-LVA.
+// 3. The killer moves, and then the countermove, if they can be played.
+// 4. The quiet moves, sorted by their history.
+// 5. The captures that lose material, as found by SEE.
+//
+// Each move is handed out only once: a later stage skips the moves that
+// were already handed out by an earlier one.
+
+use super::{
+    defs::{SearchRefs, MAX_KILLER_MOVES},
+    history::PreviousMoves,
+    Search,
+};
+use crate::{
+    board::defs::Pieces,
+    movegen::defs::{Move, MoveList, MoveType, ShortMove},
+};
+
+#[derive(PartialEq, Copy, Clone)]
+enum Stage {
+    TtMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Killers,
+    CounterMove,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+pub struct MovePicker {
+    stage: Stage,
+    tt_move: ShortMove,
+    killers: [ShortMove; MAX_KILLER_MOVES],
+    counter_move: ShortMove,
+    captures: MoveList,
+    quiets: MoveList,
+    capture_index: u8,
+    quiet_index: u8,
+    killer_index: usize,
+}
+
+impl MovePicker {
+    pub fn new(tt_move: ShortMove, refs: &SearchRefs) -> Self {
+        let ply = refs.search_info.ply as usize;
+        let previous: PreviousMoves = Search::previous_moves(refs.board);
+
+        Self {
+            stage: Stage::TtMove,
+            tt_move,
+            killers: refs.search_info.killer_moves[ply],
+            counter_move: Search::counter_move(&previous, refs),
+            captures: MoveList::new(),
+            quiets: MoveList::new(),
+            capture_index: 0,
+            quiet_index: 0,
+            killer_index: 0,
+        }
+    }
+
+    // Returns the next move to search, or None if there are no more.
+    pub fn next(&mut self, refs: &SearchRefs) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TtMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    let m = Move::new(self.tt_move.get_move() as usize);
+                    if self.tt_move.get_move() != 0 && refs.mg.is_pseudo_legal(refs.board, m) {
+                        return Some(m);
+                    }
+                }
+
+                Stage::GenerateCaptures => {
+                    let mt = MoveType::Capture;
+                    refs.mg.generate_moves(refs.board, &mut self.captures, mt);
+                    Search::score_moves(&mut self.captures, self.tt_move, refs);
+                    self.stage = Stage::GoodCaptures;
+                }
+
+                // The captures are picked in sorting order. The first
+                // losing capture ends this stage; the rest are losing too.
+                Stage::GoodCaptures => {
+                    if self.capture_index >= self.captures.len() {
+                        self.stage = Stage::Killers;
+                        continue;
+                    }
+
+                    Search::pick_move(&mut self.captures, self.capture_index);
+                    let m = self.captures.get_move(self.capture_index);
+                    if Search::is_sorted_as_losing(m) {
+                        self.stage = Stage::Killers;
+                        continue;
+                    }
+
+                    self.capture_index += 1;
+                    if !self.is_tt_move(m) {
+                        return Some(m);
+                    }
+                }
+
+                Stage::Killers => {
+                    if self.killer_index >= MAX_KILLER_MOVES {
+                        self.stage = Stage::CounterMove;
+                        continue;
+                    }
+
+                    let killer = self.killers[self.killer_index];
+                    self.killer_index += 1;
+                    if let Some(m) = self.playable_quiet(killer, refs) {
+                        return Some(m);
+                    }
+                }
+
+                Stage::CounterMove => {
+                    self.stage = Stage::GenerateQuiets;
+                    let m = Move::new(self.counter_move.get_move() as usize);
+                    if !self.is_killer(m) {
+                        if let Some(m) = self.playable_quiet(self.counter_move, refs) {
+                            return Some(m);
+                        }
+                    }
+                }
+
+                Stage::GenerateQuiets => {
+                    let mt = MoveType::Quiet;
+                    refs.mg.generate_moves(refs.board, &mut self.quiets, mt);
+                    Search::score_moves(&mut self.quiets, self.tt_move, refs);
+                    self.stage = Stage::Quiets;
+                }
+
+                Stage::Quiets => {
+                    if self.quiet_index >= self.quiets.len() {
+                        self.stage = Stage::BadCaptures;
+                        continue;
+                    }
+
+                    Search::pick_move(&mut self.quiets, self.quiet_index);
+                    let m = self.quiets.get_move(self.quiet_index);
+                    self.quiet_index += 1;
+
+                    let is_handed_out = self.is_tt_move(m)
+                        || self.is_killer(m)
+                        || m.get_move() == self.counter_move.get_move();
+                    if !is_handed_out {
+                        return Some(m);
+                    }
+                }
+
+                Stage::BadCaptures => {
+                    if self.capture_index >= self.captures.len() {
+                        self.stage = Stage::Done;
+                        continue;
+                    }
+
+                    Search::pick_move(&mut self.captures, self.capture_index);
+                    let m = self.captures.get_move(self.capture_index);
+                    self.capture_index += 1;
+                    if !self.is_tt_move(m) {
+                        return Some(m);
+                    }
+                }
+
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    fn is_tt_move(&self, m: Move) -> bool {
+        m.get_move() == self.tt_move.get_move()
+    }
+
+    fn is_killer(&self, m: Move) -> bool {
+        self.killers.iter().any(|k| k.get_move() == m.get_move())
+    }
+
+    // Killers and the countermove were stored in other positions. They
+    // are only handed out if they are quiet moves that can be played in
+    // this position. (An en-passant capture is generated with the
+    // captures, so it has already been handed out.)
+    fn playable_quiet(&self, short_move: ShortMove, refs: &SearchRefs) -> Option<Move> {
+        let m = Move::new(short_move.get_move() as usize);
+        let is_quiet = m.captured() == Pieces::NONE && !m.en_passant();
+        let is_new = short_move.get_move() != 0 && !self.is_tt_move(m);
+
+        if is_quiet && is_new && refs.mg.is_pseudo_legal(refs.board, m) {
+            Some(m)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,93 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2021, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// null_move.rs contains null move pruning. The side to move passes the
+// turn, and the opponent gets a reduced search to get the score below
+// beta. If even that fails, the position is so good that searching the
+// real moves would fail high as well, and the node can be cut off. This
+// goes wrong in zugzwang, where passing would be the best move if it
+// were allowed. That is why the null move is not tried with only king
+// and pawns left, and why the cutoff can be verified by a reduced search
+// of the real moves.
+
+use super::{
+    defs::{
+        SearchRefs, CHECKMATE_THRESHOLD, NULL_MOVE_DEPTH_DIVISOR, NULL_MOVE_MIN_DEPTH,
+        NULL_MOVE_REDUCTION, NULL_MOVE_VERIFY_DEPTH,
+    },
+    Search,
+};
+use crate::movegen::defs::Move;
+
+impl Search {
+    // The caller checks that the node is not the root, not a PV-node, and
+    // not in check.
+    pub fn is_null_move_allowed(depth: i8, refs: &SearchRefs) -> bool {
+        depth >= NULL_MOVE_MIN_DEPTH
+            && !refs.search_info.verifying
+            && !refs.board.last_move_is_null()
+            && refs.board.has_non_pawn_material(refs.board.us())
+            // Searching for a mate needs every move to be searched, as a
+            // null move can hide a mate through zugzwang.
+            && refs.search_params.mate.is_none()
+    }
+
+    // Returns the score to cut off the node with, if the null move fails
+    // high. The reduction grows with the depth.
+    pub fn null_move(depth: i8, beta: i16, refs: &mut SearchRefs) -> Option<i16> {
+        let reduction = NULL_MOVE_REDUCTION + depth / NULL_MOVE_DEPTH_DIVISOR;
+        let mut node_pv: Vec<Move> = Vec::new();
+
+        refs.board.make_null();
+        refs.search_info.ply += 1;
+        let score =
+            -Search::alpha_beta(depth - 1 - reduction, -beta, -beta + 1, &mut node_pv, refs);
+        refs.board.unmake_null();
+        refs.search_info.ply -= 1;
+
+        if refs.search_info.interrupted() || score < beta {
+            return None;
+        }
+
+        // Verify the cutoff by searching the real moves, at a reduced depth
+        // and without null moves. If that doesn't fail high, there may be
+        // a zugzwang, and the node is searched normally.
+        if refs.search_params.verification && depth >= NULL_MOVE_VERIFY_DEPTH {
+            refs.search_info.verifying = true;
+            let verified =
+                Search::alpha_beta(depth - reduction, beta - 1, beta, &mut node_pv, refs);
+            refs.search_info.verifying = false;
+
+            if verified < beta {
+                return None;
+            }
+        }
+
+        // A mate found after passing the turn is not a real mate.
+        if score >= CHECKMATE_THRESHOLD {
+            Some(beta)
+        } else {
+            Some(score)
+        }
+    }
+}
@@ -96,6 +96,14 @@ impl Search {
             Search::pick_move(&mut move_list, i);
 
             let current_move = move_list.get_move(i);
+
+            // Captures that lose material can't raise alpha above the
+            // stand-pat score. They are sorted last, so the rest of the
+            // captures don't have to be searched.
+            if Search::is_sorted_as_losing(current_move) {
+                break;
+            }
+
             let is_legal = refs.board.make(current_move, refs.mg);
 
             // If not legal, skip the move and the rest of the function.
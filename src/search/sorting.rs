@@ -24,14 +24,24 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 // Move sorting routines.
 
 use super::{
-    defs::{SearchRefs, MAX_KILLER_MOVES},
+    defs::{SearchRefs, CONTINUATION_PLIES, MAX_HISTORY, MAX_KILLER_MOVES},
     Search,
 };
-use crate::{board::defs::Pieces, defs::NrOf, movegen::defs::MoveList, movegen::defs::ShortMove};
+use crate::{
+    board::defs::Pieces,
+    defs::NrOf,
+    evaluation::defs::PIECE_VALUES,
+    movegen::defs::{Move, MoveList, ShortMove},
+};
 
 const MVV_LVA_OFFSET: u32 = u32::MAX - 256;
 const TTMOVE_SORT_VALUE: u32 = 60;
 const KILLER_VALUE: u32 = 10;
+const QUIET_OFFSET: u32 = 256; // Quiet moves sort above losing captures
+const COUNTER_MOVE_VALUE: u32 = MVV_LVA_OFFSET - (MAX_KILLER_MOVES as u32 + 1) * KILLER_VALUE;
+
+// History scores can be negative. This makes them positive for sorting.
+const HISTORY_RANGE: i32 = (1 + CONTINUATION_PLIES as i32) * MAX_HISTORY;
 
 // MVV_VLA[victim][attacker]
 pub const MVV_LVA: [[u16; NrOf::PIECE_TYPES + 1]; NrOf::PIECE_TYPES + 1] = [
@@ -46,17 +56,29 @@ pub const MVV_LVA: [[u16; NrOf::PIECE_TYPES + 1]; NrOf::PIECE_TYPES + 1] = [
 
 impl Search {
     pub fn score_moves(ml: &mut MoveList, tt_move: ShortMove, refs: &SearchRefs) {
+        let previous = Search::previous_moves(refs.board);
+        let counter_move = Search::counter_move(&previous, refs);
+
         for i in 0..ml.len() {
             let m = ml.get_mut_move(i);
             let mut value: u32 = 0;
 
-            // Sort order priority is: TT Move first, then captures, then
-            // quiet moves that are in the list of killer moves.
+            // Sort order priority is: TT Move first, then captures that
+            // don't lose material, then quiet moves that are in the list
+            // of killer moves, then the countermove, then other quiet
+            // moves by their history, and then captures that lose
+            // material.
             if m.get_move() == tt_move.get_move() {
                 value = MVV_LVA_OFFSET + TTMOVE_SORT_VALUE;
             } else if m.captured() != Pieces::NONE {
-                // Order captures higher than MVV_LVA_OFFSET
-                value = MVV_LVA_OFFSET + MVV_LVA[m.captured()][m.piece()] as u32;
+                let mvv_lva = MVV_LVA[m.captured()][m.piece()] as u32;
+                if Search::is_losing_capture(*m, refs) {
+                    // Order losing captures below the quiet moves.
+                    value = mvv_lva;
+                } else {
+                    // Order captures higher than MVV_LVA_OFFSET
+                    value = MVV_LVA_OFFSET + mvv_lva;
+                }
             } else {
                 let ply = refs.search_info.ply as usize;
                 let mut n = 0;
@@ -64,25 +86,40 @@ impl Search {
                     let killer = refs.search_info.killer_moves[ply][n];
                     if m.get_move() == killer.get_move() {
                         // Order killers below MVV_LVA_OFFSET
-                        value = MVV_LVA_OFFSET - ((i as u32 + 1) * KILLER_VALUE);
+                        value = MVV_LVA_OFFSET - ((n as u32 + 1) * KILLER_VALUE);
                     }
                     n += 1;
                 }
-            }
 
-            /*
-                // If still not sorted, try to sort by history heuristic.
+                if value == 0 && m.get_move() == counter_move.get_move() {
+                    value = COUNTER_MOVE_VALUE;
+                }
+
+                // Other quiet moves are sorted by their history.
                 if value == 0 {
-                    let piece = m.piece();
-                    let to = m.to();
-                    value = refs.search_info.history_heuristic[refs.board.us()][piece][to];
+                    let history = Search::history_score(*m, &previous, refs);
+                    value = QUIET_OFFSET + (history + HISTORY_RANGE) as u32;
                 }
-            */
+            }
 
             m.set_sort_score(value);
         }
     }
 
+    // Losing captures are the only moves sorted below the quiet moves.
+    pub fn is_sorted_as_losing(m: Move) -> bool {
+        m.get_sort_score() < QUIET_OFFSET
+    }
+
+    // A capture with a more valuable piece than the one captured may lose
+    // material. Only then the static exchange evaluation is needed. (An
+    // en-passant capture is pawn takes pawn, so it never loses.)
+    pub fn is_losing_capture(m: Move, refs: &SearchRefs) -> bool {
+        m.captured() != Pieces::NONE
+            && PIECE_VALUES[m.piece()] > PIECE_VALUES[m.captured()]
+            && refs.mg.see(refs.board, m) < 0
+    }
+
     // This function puts the move with the highest sort score at the
     // "start_index" position, where alpha-beta will pick the next move.
     pub fn pick_move(ml: &mut MoveList, start_index: u8) {
@@ -29,11 +29,12 @@ const GAME_LENGTH: usize = 25; // moves
 const MOVES_BUFFER: usize = 5; //moves
 const CRITICAL_TIME: u128 = 1_000; // msecs
 const OK_TIME: u128 = CRITICAL_TIME * 5; // msecs
+const FAIL_LOW_FACTOR: f64 = 1.5;
 
 impl Search {
     // Determine if allocated search time has been used up.
     pub fn out_of_time(refs: &mut SearchRefs) -> bool {
-        let elapsed = refs.search_info.timer_elapsed();
+        let elapsed = refs.search_info.time_used();
         let allocated = refs.search_info.allocated_time;
 
         // Calculate a factor with which it is allowed to overshoot the
@@ -46,14 +47,57 @@ impl Search {
             _ => 1.0,                                      // This case shouldn't happen.
         };
 
+        // If the score dropped below the aspiration window, the best move
+        // may be bad. Take more time to find a better one, unless the
+        // time is critical.
+        let overshoot_factor = if refs.search_info.fail_low && allocated > CRITICAL_TIME {
+            overshoot_factor * FAIL_LOW_FACTOR
+        } else {
+            overshoot_factor
+        };
+
         elapsed >= (overshoot_factor * allocated as f64).round() as u128
     }
 
+    // The opponent played the expected move. The search continues, but
+    // from now on, the engine's clock is running.
+    pub fn ponder_hit(refs: &mut SearchRefs) {
+        refs.search_params.ponder = false;
+        refs.search_info.ponder_hit_at = refs.search_info.timer_elapsed();
+
+        // Now the clock is running, determine the time for this move. If
+        // there is no time, the search stops at the next check and plays
+        // the best move found so far.
+        if refs.search_params.is_game_time() {
+            Search::allocate_time(refs);
+        }
+    }
+
+    // Determines the time to spend on this move in GameTime mode. Returns
+    // false if there is no time available at all.
+    pub fn allocate_time(refs: &mut SearchRefs) -> bool {
+        // Determine the maximum time slice available for this move.
+        let time_slice = Search::calculate_time_slice(refs);
+
+        // Experience reveals that after using about 40-50% of the
+        // available time, the next depth will not be finished, so
+        // don't allocated more than 40% of the calculated move time.
+        let factor = 0.40;
+        refs.search_info.allocated_time = (time_slice as f64 * factor).round() as u128;
+
+        time_slice > 0
+    }
+
     // Calculates the time the engine allocates for searching a single
     // move. This depends on the number of moves still to go in the game.
     pub fn calculate_time_slice(refs: &SearchRefs) -> u128 {
+        // Without game time, there is no time slice.
+        let gt = match &refs.search_params.game_time {
+            Some(gt) => gt,
+            None => return 0,
+        };
+
         // Calculate the time slice step by step.
-        let gt = &refs.search_params.game_time;
         let mtg = Search::moves_to_go(refs);
         let white = refs.board.us() == Sides::WHITE;
         let clock = if white { gt.wtime } else { gt.btime };
@@ -79,7 +123,7 @@ impl Search {
     // to go", if this value is not supplied.
     fn moves_to_go(refs: &SearchRefs) -> usize {
         // If moves to go was supplied, then use this.
-        if let Some(x) = refs.search_params.game_time.moves_to_go {
+        if let Some(x) = refs.search_params.game_time.and_then(|gt| gt.moves_to_go) {
             x
         } else {
             // Guess moves to go if not supplied.
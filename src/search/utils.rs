@@ -23,8 +23,8 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use super::{
     defs::{
-        SearchControl, SearchCurrentMove, SearchMode, SearchRefs, SearchReport, SearchStats,
-        SearchTerminate, MAX_KILLER_MOVES, MIN_TIME_CURR_MOVE, MIN_TIME_STATS,
+        SearchControl, SearchCurrentMove, SearchRefs, SearchReport, SearchStats, SearchTerminate,
+        MAX_KILLER_MOVES, MIN_TIME_CURR_MOVE, MIN_TIME_STATS,
     },
     Search,
 };
@@ -52,7 +52,7 @@ impl Search {
         let last_stats = refs.search_info.last_stats_sent;
 
         if elapsed >= last_stats + MIN_TIME_STATS {
-            let hash_full = refs.tt.lock().expect(ErrFatal::LOCK).hash_full();
+            let hash_full = refs.tt.hash_full();
             let msecs = refs.search_info.timer_elapsed();
             let nps = Search::nodes_per_second(refs.search_info.nodes, msecs);
             let stats = SearchStats::new(msecs, refs.search_info.nodes, nps, hash_full);
@@ -85,40 +85,37 @@ impl Search {
         // Terminate search if stop or quit command is received.
         let cmd = refs.control_rx.try_recv().unwrap_or(SearchControl::Nothing);
         match cmd {
+            SearchControl::PonderHit => Search::ponder_hit(refs),
             SearchControl::Stop => refs.search_info.terminate = SearchTerminate::Stop,
             SearchControl::Quit => refs.search_info.terminate = SearchTerminate::Quit,
             SearchControl::Start(_) | SearchControl::Nothing => (),
         };
 
-        // Terminate search if certain conditions are met.
-        let search_mode = refs.search_params.search_mode;
-        match search_mode {
-            SearchMode::Depth => {
-                if refs.search_info.depth > refs.search_params.depth {
-                    refs.search_info.terminate = SearchTerminate::Stop
-                }
-            }
-            SearchMode::MoveTime => {
-                let elapsed = refs.search_info.timer_elapsed();
-                if elapsed >= refs.search_params.move_time {
-                    refs.search_info.terminate = SearchTerminate::Stop
-                }
-            }
-            SearchMode::Nodes => {
-                if refs.search_info.nodes >= refs.search_params.nodes {
-                    refs.search_info.terminate = SearchTerminate::Stop
-                }
-            }
-            SearchMode::GameTime => {
-                if Search::out_of_time(refs) {
-                    refs.search_info.terminate = SearchTerminate::Stop
-                }
-            }
-            SearchMode::Infinite => (), // Handled by a direct 'stop' command
-            SearchMode::Nothing => (),  // We're not searching. Nothing to do.
+        // Terminate search as soon as one of the limits is reached.
+        let sp = &refs.search_params;
+        let si = &refs.search_info;
+        let depth_reached = si.depth > sp.max_depth();
+        let time_up = sp.move_time.is_some_and(|t| si.timer_elapsed() >= t);
+        let nodes_reached = sp.nodes.is_some_and(|n| si.nodes >= n);
+        let out_of_time = sp.is_game_time() && !sp.ponder && Search::out_of_time(refs);
+
+        if depth_reached || time_up || nodes_reached || out_of_time {
+            refs.search_info.terminate = SearchTerminate::Stop
         }
     }
 
+    // Root moves can be excluded because they were already found in
+    // MultiPV mode, or because the GUI restricted the search to a set of
+    // moves using "go searchmoves".
+    pub fn is_excluded_root_move(m: Move, refs: &SearchRefs) -> bool {
+        let sm = &refs.search_params.search_moves;
+        let not_in_search_moves =
+            !sm.is_empty() && !sm.iter().any(|x| x.get_move() == m.get_move());
+        let already_found = refs.search_info.excluded_moves.contains(&m.to_short_move());
+
+        not_in_search_moves || already_found
+    }
+
     // Returns true if the position should be evaluated as a draw.
     pub fn is_draw(refs: &SearchRefs) -> bool {
         let is_max_move_rule = refs.board.game_state.halfmove_clock >= MAX_MOVE_RULE;